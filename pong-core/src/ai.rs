@@ -0,0 +1,222 @@
+//! Computer-controlled paddles.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::controller::{steer_towards, ControlContext, PaddleController};
+use crate::rng::Rng;
+use crate::{Ball, Paddle};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Perfect,
+}
+
+/// What separates one difficulty from another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AiSettings {
+    /// Seconds between looks at the ball. The paddle heads for its last guess in between.
+    pub reaction_delay: f32,
+    /// The most, in arena units, a guess of where to meet the ball can be off by.
+    pub aim_error: f32,
+    /// Fastest the paddle moves, as an axis value in `0.0..=1.0`.
+    pub max_speed: f32,
+}
+
+impl Difficulty {
+    pub fn settings(self) -> AiSettings {
+        match self {
+            Difficulty::Easy => AiSettings {
+                reaction_delay: 0.4,
+                aim_error: 10.0,
+                max_speed: 0.5,
+            },
+            Difficulty::Medium => AiSettings {
+                reaction_delay: 0.25,
+                aim_error: 6.0,
+                max_speed: 0.7,
+            },
+            Difficulty::Hard => AiSettings {
+                reaction_delay: 0.1,
+                aim_error: 3.0,
+                max_speed: 0.9,
+            },
+            Difficulty::Perfect => AiSettings {
+                reaction_delay: 0.0,
+                aim_error: 0.0,
+                max_speed: 1.0,
+            },
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Difficulty, String> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            "perfect" => Ok(Difficulty::Perfect),
+            other => Err(format!(
+                "unknown difficulty `{}`, expected easy, medium, hard or perfect",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Perfect => "perfect",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Moves a paddle towards where it expects to meet the ball.
+#[derive(Clone, Debug)]
+pub struct AiController {
+    settings: AiSettings,
+    rng: Rng,
+    reaction_timer: f32,
+    target: Option<f32>,
+}
+
+impl AiController {
+    pub fn new(difficulty: Difficulty, seed: u64) -> AiController {
+        AiController::with_settings(difficulty.settings(), seed)
+    }
+
+    pub fn with_settings(settings: AiSettings, seed: u64) -> AiController {
+        AiController {
+            settings,
+            rng: Rng::new(seed),
+            reaction_timer: 0.0,
+            target: None,
+        }
+    }
+}
+
+impl PaddleController for AiController {
+    fn movement(&mut self, context: &ControlContext<'_>) -> f32 {
+        let paddle = context.paddle;
+        self.reaction_timer -= context.dt;
+        if self.reaction_timer <= 0.0 || self.target.is_none() {
+            self.reaction_timer = self.settings.reaction_delay;
+            let error = self.rng.range(-self.settings.aim_error, self.settings.aim_error);
+            self.target = Some(match context.ball {
+                Some(ball) if heading_towards(ball, paddle) => {
+                    predict_intercept(ball, paddle, context.arena_height) + error
+                }
+                // nothing to chase, wait in the middle
+                _ => context.arena_height / 2.0,
+            });
+        }
+
+        let target = self.target.unwrap_or(paddle.position[1]);
+        steer_towards(paddle, target) * self.settings.max_speed
+    }
+}
+
+fn heading_towards(ball: &Ball, paddle: &Paddle) -> bool {
+    (paddle.position[0] - ball.position[0]) * ball.direction[0] > 0.0
+}
+
+/// Height at which the ball will reach the face of `paddle`, following its bounces off the top
+/// and bottom walls. Spin is not taken into account.
+pub fn predict_intercept(ball: &Ball, paddle: &Paddle, arena_height: f32) -> f32 {
+    let velocity = ball.velocity();
+    let reach = paddle.width * 0.5 + ball.radius;
+    let face_x = if velocity[0] > 0.0 {
+        paddle.position[0] - reach
+    } else {
+        paddle.position[0] + reach
+    };
+    if velocity[0] == 0.0 {
+        return ball.position[1];
+    }
+    let time = ((face_x - ball.position[0]) / velocity[0]).max(0.0);
+    let y = ball.position[1] + velocity[1] * time;
+
+    // Unfold the wall bounces: the ball's centre moves between `radius` and
+    // `arena_height - radius`, so mirror y back into that band.
+    let low = ball.radius;
+    let span = arena_height - 2.0 * ball.radius;
+    if span <= 0.0 {
+        return arena_height / 2.0;
+    }
+    let folded = (y - low).rem_euclid(2.0 * span);
+    if folded <= span {
+        low + folded
+    } else {
+        low + 2.0 * span - folded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::NoInput;
+    use crate::{Arena, Event, Input, Side};
+
+    const DT: f32 = 1.0 / 120.0;
+
+    // the right paddle's movement each tick of a match against a left paddle that stands still
+    fn play(controller: &mut AiController, seed: u64, ticks: u32) -> (Vec<f32>, Vec<Event>) {
+        let mut arena = Arena::default().with_seed(seed);
+        let (mut moves, mut events) = (Vec::new(), Vec::new());
+        for _ in 0..ticks {
+            let movement = controller.movement(&arena.control_context(Side::Right, DT, &NoInput));
+            moves.push(movement);
+            let input = Input {
+                right: movement,
+                serve_left: true,
+                serve_right: true,
+                ..Input::default()
+            };
+            events.extend(arena.step(input, DT));
+        }
+        (moves, events)
+    }
+
+    #[test]
+    fn perfect_never_misses() {
+        for seed in 0..10 {
+            let (_, events) = play(&mut AiController::new(Difficulty::Perfect, seed), seed, 12_000);
+            assert!(events.contains(&Event::Point(Side::Right)), "seed {}", seed);
+            assert!(!events.contains(&Event::Point(Side::Left)), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_moves() {
+        let (first, _) = play(&mut AiController::new(Difficulty::Easy, 3), 5, 3000);
+        let (second, _) = play(&mut AiController::new(Difficulty::Easy, 3), 5, 3000);
+        assert_eq!(first, second);
+        // only the aim changes with the seed
+        let (other, _) = play(&mut AiController::new(Difficulty::Easy, 4), 5, 3000);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn intercepts_unfold_wall_bounces() {
+        let config = crate::GameConfig::default();
+        let paddle = Paddle::new(Side::Right, &config);
+        let mut ball = Ball::new(&config, 100.0);
+        ball.teleport([paddle.position[0] - 50.0, 10.0]);
+        // 45 degrees down: 50 across is 50 down, less the paddle and ball, off the floor
+        ball.set_heading(-45.0, 90.0);
+        let reach = paddle.width * 0.5 + ball.radius;
+        let expected = ball.radius + (50.0 - reach - (10.0 - ball.radius));
+        assert!((predict_intercept(&ball, &paddle, 100.0) - expected).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::bounce::{bounce_off_paddle, bounce_off_walls};
+use crate::controller::{ControlContext, InputSource};
+use crate::rules::{MatchRules, MatchState};
+use crate::hash::Fnv;
+use crate::score::check_point;
+use crate::{Ball, GameConfig, Paddle, PhysicsConfig, ScoreBoard, Serve, Side};
+
+/// Player input for one step: paddle movement as an axis value in `-1.0..=1.0` per side, and
+/// whether each side pressed serve.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Input {
+    pub left: f32,
+    pub right: f32,
+    pub serve_left: bool,
+    pub serve_right: bool,
+}
+
+impl Input {
+    pub fn for_side(&self, side: Side) -> f32 {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+        }
+    }
+
+    pub fn serve(&self, side: Side) -> bool {
+        match side {
+            Side::Left => self.serve_left,
+            Side::Right => self.serve_right,
+        }
+    }
+}
+
+/// Something that happened during a step, for callers that play sounds or update ui.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Bounce,
+    /// This side served the ball.
+    Served(Side),
+    Point(Side),
+    /// The match is over and this side won it.
+    Won(Side),
+}
+
+/// A complete match: the arena bounds, both paddles, the ball, the score and the rules that
+/// decide when it ends.
+#[derive(Clone, Debug)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+    pub physics: PhysicsConfig,
+    pub rules: MatchRules,
+    pub left: Paddle,
+    pub right: Paddle,
+    pub ball: Ball,
+    pub scores: ScoreBoard,
+    pub state: MatchState,
+    pub serve: Serve,
+}
+
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena::new(&GameConfig::default(), PhysicsConfig::default(), MatchRules::default())
+    }
+}
+
+impl Arena {
+    /// Sets up a match with the ball held for the first serve. Serves are drawn from seed 0,
+    /// see `with_seed`.
+    pub fn new(game: &GameConfig, physics: PhysicsConfig, rules: MatchRules) -> Arena {
+        let mut ball = Ball::new(game, physics.ball_speed);
+        ball.hold(ball.position);
+        let serve = Serve::new(0, &rules);
+        Arena {
+            width: game.arena_width,
+            height: game.arena_height,
+            physics,
+            rules,
+            left: Paddle::new(Side::Left, game),
+            right: Paddle::new(Side::Right, game),
+            ball,
+            scores: ScoreBoard::default(),
+            state: MatchState::default(),
+            serve,
+        }
+    }
+
+    /// Restarts the serve sequence from `seed`.
+    pub fn with_seed(mut self, seed: u64) -> Arena {
+        self.serve = Serve::new(seed, &self.rules);
+        self
+    }
+
+    pub fn paddle(&self, side: Side) -> &Paddle {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    /// What the controller for `side` gets to see before a step of `dt` seconds.
+    pub fn control_context<'a>(&'a self, side: Side, dt: f32, input: &'a dyn InputSource) -> ControlContext<'a> {
+        ControlContext {
+            paddle: self.paddle(side),
+            opponent: Some(self.paddle(side.other())),
+            ball: Some(&self.ball),
+            arena_height: self.height,
+            scores: &self.scores,
+            dt,
+            input,
+        }
+    }
+
+    /// A fingerprint of everything that moves during a match. Two arenas that were stepped with
+    /// the same inputs have the same checksum, down to the last bit of every float.
+    pub fn checksum(&self) -> u64 {
+        let mut hash = Fnv::default();
+        for value in self.ball.position.iter().chain(self.ball.direction.iter()) {
+            hash.write_u32(value.to_bits());
+        }
+        hash.write_u32(self.ball.speed.to_bits());
+        hash.write_u32(self.ball.spin.to_bits());
+        hash.write_u32(self.ball.rally_hits);
+        for paddle in [&self.left, &self.right].iter() {
+            hash.write_u32(paddle.position[1].to_bits());
+            hash.write_u32(paddle.velocity.to_bits());
+        }
+        hash.write_u32(self.scores.score_left as u32);
+        hash.write_u32(self.scores.score_right as u32);
+        hash.write_u32(self.serve.server as u32);
+        hash.write_u32(self.serve.countdown.map_or(u32::MAX, f32::to_bits));
+        hash.write_u32(self.state.elapsed.to_bits());
+        hash.finish()
+    }
+
+    /// Advances the match by `dt` seconds. This runs the same steps as the paddle, serve, ball,
+    /// bounce and winner systems, in the same order. Once the match is over nothing moves.
+    pub fn step(&mut self, input: Input, dt: f32) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.state.is_over() {
+            return events;
+        }
+
+        for paddle in [&mut self.left, &mut self.right].iter_mut() {
+            paddle.apply_input(input.for_side(paddle.side), self.height, dt);
+        }
+
+        let server = self.serve.server;
+        if self.serve.update(&mut self.ball, input.serve(server), &self.rules, &self.physics, dt) {
+            events.push(Event::Served(server));
+        }
+
+        self.ball.advance(dt, &self.physics);
+
+        if bounce_off_walls(&mut self.ball, self.height) {
+            events.push(Event::Bounce);
+        }
+        for paddle in [&self.left, &self.right].iter() {
+            if bounce_off_paddle(&mut self.ball, paddle, &self.physics) {
+                events.push(Event::Bounce);
+            }
+        }
+
+        if let Some(side) = check_point(&mut self.ball, self.width, self.height, &mut self.scores) {
+            self.serve.after_point(side, &self.rules);
+            events.push(Event::Point(side));
+        }
+        if let Some(side) = self.state.update(&self.rules, &self.scores, dt) {
+            events.push(Event::Won(side));
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // moves both paddles up and down at different rates and serves straight away
+    fn input(tick: u32) -> Input {
+        let t = tick as f32 * 0.05;
+        Input {
+            left: t.sin(),
+            right: (t * 1.3).cos(),
+            serve_left: true,
+            serve_right: true,
+        }
+    }
+
+    fn play(seed: u64, ticks: u32) -> (Arena, Vec<Event>) {
+        let mut arena = Arena::default().with_seed(seed);
+        let mut events = Vec::new();
+        for tick in 0..ticks {
+            events.extend(arena.step(input(tick), 1.0 / 120.0));
+        }
+        (arena, events)
+    }
+
+    #[test]
+    fn step_is_deterministic_for_a_seed() {
+        let (first, first_events) = play(7, 6000);
+        let (second, second_events) = play(7, 6000);
+        assert_eq!(first.checksum(), second.checksum());
+        assert_eq!(first_events, second_events);
+        assert!(first_events.iter().any(|event| matches!(event, Event::Point(_))));
+    }
+
+    #[test]
+    fn seed_changes_the_match() {
+        assert_ne!(play(7, 600).0.checksum(), play(8, 600).0.checksum());
+    }
+
+    #[test]
+    fn nothing_moves_once_the_match_is_over() {
+        let mut arena = Arena::default();
+        arena.state.winner = Some(Side::Left);
+        let checksum = arena.checksum();
+        assert!(arena.step(input(0), 1.0 / 120.0).is_empty());
+        assert_eq!(arena.checksum(), checksum);
+    }
+}
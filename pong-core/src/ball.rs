@@ -0,0 +1,85 @@
+use crate::timestep::lerp;
+use crate::{GameConfig, PhysicsConfig};
+
+#[derive(Clone, Debug)]
+pub struct Ball {
+    pub position: [f32; 2],
+    /// Position before the last tick, for interpolated rendering.
+    pub previous_position: [f32; 2],
+    /// Speed in arena units per second.
+    pub speed: f32,
+    /// Unit vector the ball is travelling along.
+    pub direction: [f32; 2],
+    /// Paddle hits since the last point.
+    pub rally_hits: u32,
+    /// How fast the ball's path bends, in degrees per second, positive curving upwards.
+    pub spin: f32,
+    pub radius: f32,
+}
+
+impl Ball {
+    /// Creates a ball in the middle of the arena heading right at `speed`. Serves pick their
+    /// own direction, see `Serve::update`.
+    pub fn new(config: &GameConfig, speed: f32) -> Ball {
+        let position = [config.arena_width / 2.0, config.arena_height / 2.0];
+        Ball {
+            position,
+            previous_position: position,
+            speed,
+            direction: [1.0, 0.0],
+            rally_hits: 0,
+            spin: 0.0,
+            radius: config.ball_radius,
+        }
+    }
+
+    pub fn velocity(&self) -> [f32; 2] {
+        [self.direction[0] * self.speed, self.direction[1] * self.speed]
+    }
+
+    /// Angle of travel in degrees above horizontal, whichever way the ball is heading.
+    pub fn heading(&self) -> f32 {
+        self.direction[1].atan2(self.direction[0].abs()).to_degrees()
+    }
+
+    /// Points the ball `degrees` above horizontal, limited to `max_angle` either way, without
+    /// changing whether it travels left or right.
+    pub fn set_heading(&mut self, degrees: f32, max_angle: f32) {
+        let angle = degrees.clamp(-max_angle, max_angle).to_radians();
+        let away = if self.direction[0] < 0.0 { -1.0 } else { 1.0 };
+        self.direction = [away * angle.cos(), angle.sin()];
+    }
+
+    /// Moves the ball along its velocity for `dt` seconds. Spin bends the path a little every
+    /// tick and wears off over time.
+    pub fn advance(&mut self, dt: f32, physics: &PhysicsConfig) {
+        if self.spin != 0.0 {
+            self.set_heading(self.heading() + self.spin * dt, physics.max_bounce_angle);
+            self.spin *= (1.0 - physics.spin_decay * dt).max(0.0);
+        }
+
+        let velocity = self.velocity();
+        self.previous_position = self.position;
+        self.position[0] += velocity[0] * dt;
+        self.position[1] += velocity[1] * dt;
+    }
+
+    /// Stops the ball at `position` ready to be served, forgetting the last rally.
+    pub fn hold(&mut self, position: [f32; 2]) {
+        self.speed = 0.0;
+        self.rally_hits = 0;
+        self.spin = 0.0;
+        self.teleport(position);
+    }
+
+    /// Moves the ball without a trail, so rendering does not sweep it across the arena.
+    pub fn teleport(&mut self, position: [f32; 2]) {
+        self.position = position;
+        self.previous_position = position;
+    }
+
+    /// Where to draw the ball when the clock is `alpha` of the way to the next tick.
+    pub fn interpolated_position(&self, alpha: f32) -> [f32; 2] {
+        lerp(self.previous_position, self.position, alpha)
+    }
+}
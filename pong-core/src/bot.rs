@@ -0,0 +1,433 @@
+//! Paddles played by other programs.
+//!
+//! `BotController` listens on a TCP port, and whichever program connects to it plays the
+//! paddle, so bots can be written in any language. The protocol is plain text, one line per
+//! message, numbers in arena units with `y` growing upwards:
+//!
+//! ```text
+//! pong -> bot  hello <side> <arena height> <tick seconds>
+//! pong -> bot  tick <n> <paddle y> <opponent y> <ball x> <ball y> <ball vx> <ball vy> <own score> <opponent score>
+//! bot -> pong  <n> <movement> [serve]
+//! ```
+//!
+//! `hello` is sent once when the bot connects, then a `tick` line every tick. The bot answers
+//! each with the tick's number, a movement in `-1.0..=1.0` and `serve` if it wants to serve.
+//! An answer that doesn't arrive within `BotSettings::timeout` is too late for its tick and is
+//! skipped when it does arrive; the paddle plays the `Fallback` for that tick instead. A bot
+//! that disconnects can connect again. Unknown values, e.g. the opponent before it exists, are
+//! sent as `-`.
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::controller::{ControlContext, PaddleController};
+use crate::{AiController, Difficulty, Side};
+
+// how often to look for a bot while waiting for the first one to connect
+const ACCEPT_POLL: Duration = Duration::from_millis(10);
+// longest a `BotSettings` wait can be
+const MAX_WAIT_SECONDS: f32 = 3600.0;
+
+/// What a paddle does on the ticks its bot doesn't answer in time, or while no bot is
+/// connected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fallback {
+    /// Stands still.
+    Stop,
+    /// Keeps moving the way the bot last asked for.
+    Repeat,
+    /// Plays like the computer player.
+    Cpu(Difficulty),
+}
+
+impl FromStr for Fallback {
+    type Err = String;
+
+    // "stop", "repeat" or "cpu[:<difficulty>]"
+    fn from_str(s: &str) -> Result<Fallback, String> {
+        match s {
+            "stop" => Ok(Fallback::Stop),
+            "repeat" => Ok(Fallback::Repeat),
+            "cpu" => Ok(Fallback::Cpu(Difficulty::Medium)),
+            _ if s.starts_with("cpu:") => Ok(Fallback::Cpu(s["cpu:".len()..].parse()?)),
+            other => Err(format!("unknown fallback `{}`, expected stop, repeat or cpu[:difficulty]", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BotSettings {
+    /// Seconds a bot has to answer each tick.
+    pub timeout: f32,
+    /// Seconds the first tick waits for a bot to connect. The game stands still meanwhile, so
+    /// a match between bots doesn't start without them.
+    pub connect_timeout: f32,
+    pub fallback: Fallback,
+}
+
+impl Default for BotSettings {
+    fn default() -> BotSettings {
+        BotSettings {
+            timeout: 0.01,
+            connect_timeout: 10.0,
+            fallback: Fallback::Stop,
+        }
+    }
+}
+
+/// Parses one of the waits in `BotSettings`: a number of seconds from 0 up to an hour.
+pub fn parse_seconds(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(seconds) if (0.0..=MAX_WAIT_SECONDS).contains(&seconds) => Ok(seconds),
+        _ => Err(format!("`{}` is not a number of seconds from 0 to {}", value, MAX_WAIT_SECONDS)),
+    }
+}
+
+// a connected bot
+struct Connection {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+    // an answer that timed out half way through arriving
+    partial: String,
+}
+
+// what the bot, or the fallback, decided for a tick
+#[derive(Clone, Copy, Default)]
+struct Decision {
+    movement: f32,
+    serve: bool,
+}
+
+/// A paddle controlled by a program connected over TCP; see the module documentation for the
+/// protocol.
+pub struct BotController {
+    listener: TcpListener,
+    settings: BotSettings,
+    connection: Option<Connection>,
+    cpu: Option<AiController>,
+    tick: u64,
+    // whether the first tick has waited for a bot yet
+    waited: bool,
+    last: Decision,
+    // the decision `serve` made for `movement` to return
+    pending: Option<Decision>,
+    missed: u64,
+}
+
+impl BotController {
+    /// Listens for a bot on `addr`.
+    pub fn listen<A: ToSocketAddrs>(addr: A, side: Side, settings: BotSettings) -> io::Result<BotController> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let cpu = match settings.fallback {
+            Fallback::Cpu(difficulty) => Some(AiController::new(difficulty, side as u64)),
+            _ => None,
+        };
+        Ok(BotController {
+            listener,
+            settings,
+            connection: None,
+            cpu,
+            tick: 0,
+            waited: false,
+            last: Decision::default(),
+            pending: None,
+            missed: 0,
+        })
+    }
+
+    /// The address bots connect to, with the port picked if `listen` was given port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Ticks the fallback played because no bot answered in time.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    fn decide(&mut self, context: &ControlContext<'_>) -> Decision {
+        self.tick += 1;
+        self.accept(context);
+        let decision = match self.ask(context) {
+            Ok(Some(decision)) => Some(decision),
+            Ok(None) => None,
+            // the bot has gone away; another one may connect
+            Err(_) => {
+                self.connection = None;
+                None
+            }
+        };
+        match decision {
+            Some(decision) => {
+                self.last = decision;
+                decision
+            }
+            None => {
+                self.missed += 1;
+                self.fallback(context)
+            }
+        }
+    }
+
+    // takes a waiting bot, if there is one and none is connected yet
+    fn accept(&mut self, context: &ControlContext<'_>) {
+        if self.connection.is_some() {
+            return;
+        }
+        let deadline = if self.waited {
+            None
+        } else {
+            self.waited = true;
+            Some(Instant::now() + Duration::from_secs_f32(self.settings.connect_timeout.max(0.0)))
+        };
+        let stream = loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => break stream,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => match deadline {
+                    Some(deadline) if Instant::now() < deadline => thread::sleep(ACCEPT_POLL),
+                    _ => return,
+                },
+                Err(_) => return,
+            }
+        };
+        if let Ok(connection) = self.greet(stream, context) {
+            self.connection = Some(connection);
+        }
+    }
+
+    fn greet(&self, stream: TcpStream, context: &ControlContext<'_>) -> io::Result<Connection> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        // a bot that stops reading mustn't hold up the game either
+        stream.set_write_timeout(Some(self.timeout()))?;
+        let mut connection = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            stream,
+            partial: String::new(),
+        };
+        let hello = format!(
+            "hello {} {} {}\n",
+            side_name(context.paddle.side),
+            context.arena_height,
+            context.dt
+        );
+        connection.stream.write_all(hello.as_bytes())?;
+        Ok(connection)
+    }
+
+    // sends the tick to the bot and waits for its answer; Ok(None) if it was too slow
+    fn ask(&mut self, context: &ControlContext<'_>) -> io::Result<Option<Decision>> {
+        let timeout = self.timeout();
+        let tick = self.tick;
+        let connection = match self.connection.as_mut() {
+            Some(connection) => connection,
+            None => return Ok(None),
+        };
+        connection.stream.write_all(state_line(tick, context).as_bytes())?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            connection.stream.set_read_timeout(Some(deadline - now))?;
+            match connection.reader.read_line(&mut connection.partial) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            }
+            if !connection.partial.ends_with('\n') {
+                continue;
+            }
+            let answer = parse_answer(&connection.partial);
+            connection.partial.clear();
+            // answers to earlier ticks arrived too late, and garbage is ignored
+            if let Some((answered, decision)) = answer {
+                if answered == tick {
+                    return Ok(Some(decision));
+                }
+            }
+        }
+    }
+
+    fn fallback(&mut self, context: &ControlContext<'_>) -> Decision {
+        match self.settings.fallback {
+            Fallback::Stop => Decision::default(),
+            Fallback::Repeat => Decision {
+                movement: self.last.movement,
+                serve: false,
+            },
+            Fallback::Cpu(_) => match self.cpu.as_mut() {
+                Some(cpu) => Decision {
+                    serve: cpu.serve(context),
+                    movement: cpu.movement(context),
+                },
+                None => Decision::default(),
+            },
+        }
+    }
+
+    fn timeout(&self) -> Duration {
+        // a zero timeout would mean waiting forever
+        Duration::from_secs_f32(self.settings.timeout.max(0.001))
+    }
+}
+
+impl PaddleController for BotController {
+    fn serve(&mut self, context: &ControlContext<'_>) -> bool {
+        let decision = self.decide(context);
+        self.pending = Some(decision);
+        decision.serve
+    }
+
+    fn movement(&mut self, context: &ControlContext<'_>) -> f32 {
+        let decision = match self.pending.take() {
+            Some(decision) => decision,
+            None => self.decide(context),
+        };
+        if decision.movement.is_finite() {
+            decision.movement.clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+fn state_line(tick: u64, context: &ControlContext<'_>) -> String {
+    let mut line = format!("tick {} {}", tick, context.paddle.position[1]);
+    let mut push = |value: Option<String>| {
+        line.push(' ');
+        line.push_str(value.as_deref().unwrap_or("-"));
+    };
+    push(context.opponent.map(|opponent| opponent.position[1].to_string()));
+    let velocity = context.ball.map(|ball| ball.velocity());
+    push(context.ball.map(|ball| ball.position[0].to_string()));
+    push(context.ball.map(|ball| ball.position[1].to_string()));
+    push(velocity.map(|velocity| velocity[0].to_string()));
+    push(velocity.map(|velocity| velocity[1].to_string()));
+    let (own, opponent) = match context.paddle.side {
+        Side::Left => (context.scores.score_left, context.scores.score_right),
+        Side::Right => (context.scores.score_right, context.scores.score_left),
+    };
+    let _ = writeln!(line, " {} {}", own, opponent);
+    line
+}
+
+// "<tick> <movement> [serve]"
+fn parse_answer(line: &str) -> Option<(u64, Decision)> {
+    let mut words = line.split_whitespace();
+    let tick = words.next()?.parse().ok()?;
+    let movement = words.next()?.parse().ok()?;
+    let serve = match words.next() {
+        Some("serve") => true,
+        Some(_) => return None,
+        None => false,
+    };
+    Some((tick, Decision { movement, serve }))
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Left => "left",
+        Side::Right => "right",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::NoInput;
+    use crate::Arena;
+
+    #[test]
+    fn answers_are_parsed() {
+        let (tick, decision) = parse_answer("12 -0.5\n").unwrap();
+        assert_eq!((tick, decision.movement, decision.serve), (12, -0.5, false));
+        let (tick, decision) = parse_answer("13 1 serve\n").unwrap();
+        assert_eq!((tick, decision.movement, decision.serve), (13, 1.0, true));
+        // a NaN gets through here; `movement` turns it into standing still
+        assert!(parse_answer("14 NaN").unwrap().1.movement.is_nan());
+    }
+
+    #[test]
+    fn garbage_answers_are_rejected() {
+        for line in ["", "\n", "hello", "12", "-1 0.5", "twelve 0.5", "12 up", "12 0.5 jump", "12 0.5 serve"] {
+            let answer = parse_answer(line);
+            assert_eq!(answer.is_some(), line == "12 0.5 serve", "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn waits_are_limited() {
+        assert_eq!(parse_seconds("0.25"), Ok(0.25));
+        assert_eq!(parse_seconds("0"), Ok(0.0));
+        for value in ["inf", "NaN", "1e30", "-1", "3601", "soon"] {
+            assert!(parse_seconds(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn fallbacks_are_parsed() {
+        assert_eq!("stop".parse(), Ok(Fallback::Stop));
+        assert_eq!("repeat".parse(), Ok(Fallback::Repeat));
+        assert_eq!("cpu".parse(), Ok(Fallback::Cpu(Difficulty::Medium)));
+        assert_eq!("cpu:hard".parse(), Ok(Fallback::Cpu(Difficulty::Hard)));
+        assert!("cpu:godlike".parse::<Fallback>().is_err());
+        assert!("run".parse::<Fallback>().is_err());
+    }
+
+    #[test]
+    fn bot_plays_over_tcp() {
+        let settings = BotSettings {
+            timeout: 0.05,
+            connect_timeout: 2.0,
+            fallback: Fallback::Repeat,
+        };
+        let mut controller = BotController::listen("127.0.0.1:0", Side::Left, settings).unwrap();
+        let addr = controller.local_addr().unwrap();
+        let arena = Arena::default();
+        let context = arena.control_context(Side::Left, 0.01, &NoInput);
+        let mut tick = || (controller.serve(&context), controller.movement(&context));
+
+        // the bot writes each answer before the tick is asked for, so the test needs no thread
+        let mut bot = TcpStream::connect(addr).unwrap();
+        bot.write_all(b"1 0.5\n").unwrap();
+        assert_eq!(tick(), (false, 0.5));
+        let mut lines = BufReader::new(bot.try_clone().unwrap()).lines();
+        assert!(lines.next().unwrap().unwrap().starts_with("hello left "));
+        assert!(lines.next().unwrap().unwrap().starts_with("tick 1 "));
+
+        // no answer in time: the fallback repeats the last movement
+        assert_eq!(tick(), (false, 0.5));
+        // the late answer to tick 2 is skipped
+        bot.write_all(b"2 -1\n3 -0.25 serve\n").unwrap();
+        assert_eq!(tick(), (true, -0.25));
+        bot.write_all(b"4 NaN\n").unwrap();
+        assert_eq!(tick(), (false, 0.0));
+        bot.write_all(b"garbage\n5 7\n").unwrap();
+        assert_eq!(tick(), (false, 1.0));
+
+        // the bot goes away, the fallback plays, and another bot takes over
+        drop(lines);
+        drop(bot);
+        assert_eq!(tick(), (false, 1.0));
+        let mut bot = TcpStream::connect(addr).unwrap();
+        bot.write_all(b"7 -0.5\n").unwrap();
+        assert_eq!(tick(), (false, -0.5));
+        assert!(controller.is_connected());
+        assert_eq!(controller.missed(), 2);
+    }
+}
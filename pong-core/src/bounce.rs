@@ -0,0 +1,355 @@
+//! Collisions between the ball and the arena walls or paddles.
+//!
+//! Paddle collisions are swept: the ball's whole path since the last tick is tested, from
+//! `previous_position` to `position`, so a fast ball cannot pass through a paddle between two
+//! ticks.
+
+use crate::{Ball, Paddle, PhysicsConfig, Side};
+
+/// Where and when a moving circle first touches a box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Fraction of the path travelled before the impact, in `0.0..=1.0`.
+    pub time: f32,
+    /// Centre of the circle at the moment of impact.
+    pub point: [f32; 2],
+    /// Unit surface normal at the contact, pointing away from the box.
+    pub normal: [f32; 2],
+}
+
+/// Reflects the ball off the top or bottom wall. Returns whether it bounced.
+///
+/// The part of the movement that went past the wall is mirrored back into the arena. The
+/// direction is checked as well as the position so a ball that is still overlapping the wall
+/// after a bounce does not bounce again.
+pub fn bounce_off_walls(ball: &mut Ball, arena_height: f32) -> bool {
+    let bottom = ball.radius;
+    let top = arena_height - ball.radius;
+    let ball_y = ball.position[1];
+
+    if ball_y <= bottom && ball.direction[1] < 0.0 {
+        ball.position[1] = 2.0 * bottom - ball_y;
+    } else if ball_y >= top && ball.direction[1] > 0.0 {
+        ball.position[1] = 2.0 * top - ball_y;
+    } else {
+        return false;
+    }
+    ball.direction[1] = -ball.direction[1];
+    // the curve is mirrored along with the path
+    ball.spin = -ball.spin;
+    true
+}
+
+/// Bounces the ball off a paddle it ran into during the last tick. Returns whether it
+/// bounced.
+///
+/// A hit on the paddle's face sends the ball back at an angle that depends on how far from
+/// the paddle's centre it landed, up to `physics.max_bounce_angle` degrees. A hit on the
+/// paddle's top or bottom edge is a plain reflection. Either way the ball then travels the
+/// rest of the tick's distance in its new direction from the point of impact.
+///
+/// A face hit also picks up part of the paddle's vertical motion and some spin, see
+/// `PhysicsConfig::english`, and counts as a return: the rally goes on and the ball speeds up,
+/// see `PhysicsConfig::rally_speed`. An edge hit does neither.
+pub fn bounce_off_paddle(ball: &mut Ball, paddle: &Paddle, physics: &PhysicsConfig) -> bool {
+    let min = [
+        paddle.position[0] - paddle.width * 0.5,
+        paddle.position[1] - paddle.height * 0.5,
+    ];
+    let max = [
+        paddle.position[0] + paddle.width * 0.5,
+        paddle.position[1] + paddle.height * 0.5,
+    ];
+    // +1 for the left paddle, which sends the ball right, and -1 for the right paddle
+    let away = match paddle.side {
+        Side::Left => 1.0,
+        Side::Right => -1.0,
+    };
+
+    if let Some(hit) = sweep_circle_aabb(ball.previous_position, ball.position, ball.radius, min, max) {
+        if dot(ball.direction, hit.normal) >= 0.0 {
+            return false;
+        }
+        // the rest of the tick is travelled at the new speed
+        let remaining = length(sub(ball.position, hit.point)) / ball.speed;
+        if hit.normal[0] * away > 0.0 {
+            ball.direction = deflect(ball, paddle, hit.point[1], away, physics.max_bounce_angle);
+            apply_english(ball, paddle, physics);
+            speed_up(ball, physics);
+        } else {
+            // grazing the top or bottom isn't a return, so the rally and speed stay as they were
+            ball.direction = reflect(ball.direction, hit.normal);
+        }
+        ball.position = [
+            hit.point[0] + ball.direction[0] * ball.speed * remaining,
+            hit.point[1] + ball.direction[1] * ball.speed * remaining,
+        ];
+        return true;
+    }
+
+    // The ball was already overlapping the paddle when the tick started, e.g. because the
+    // paddle moved into it. There is no impact to find, so send it back towards the middle.
+    //
+    // To determine whether the ball is touching the paddle, we create a larger rectangle around
+    // the paddle, by subtracting the ball radius from the lowest coordinates, and adding the
+    // ball radius to the highest ones. The ball is then within the paddle if its center is
+    // within the larger wrapper rectangle.
+    let touching = point_in_rect(
+        ball.position[0],
+        ball.position[1],
+        min[0] - ball.radius,
+        min[1] - ball.radius,
+        max[0] + ball.radius,
+        max[1] + ball.radius,
+    );
+
+    if touching && ball.direction[0] * away < 0.0 {
+        ball.direction = deflect(ball, paddle, ball.position[1], away, physics.max_bounce_angle);
+        apply_english(ball, paddle, physics);
+        speed_up(ball, physics);
+        true
+    } else {
+        false
+    }
+}
+
+// Classic pong deflection: the further from the centre of the paddle the ball lands, the
+// steeper it leaves. Returns the new direction.
+fn deflect(ball: &Ball, paddle: &Paddle, contact_y: f32, away: f32, max_angle: f32) -> [f32; 2] {
+    let reach = paddle.height * 0.5 + ball.radius;
+    let offset = ((contact_y - paddle.position[1]) / reach).clamp(-1.0, 1.0);
+    let angle = (offset * max_angle).to_radians();
+    [away * angle.cos(), angle.sin()]
+}
+
+// Adds part of the paddle's vertical velocity to the ball's and spins it in the direction the
+// paddle was moving. The speed is kept; only the heading changes.
+fn apply_english(ball: &mut Ball, paddle: &Paddle, physics: &PhysicsConfig) {
+    let push = paddle.velocity * physics.english;
+    let heading = (ball.direction[1] * ball.speed + push).atan2(ball.direction[0].abs() * ball.speed);
+    ball.set_heading(heading.to_degrees(), physics.max_bounce_angle);
+    ball.spin += paddle.velocity * physics.spin_per_paddle_speed;
+}
+
+fn speed_up(ball: &mut Ball, physics: &PhysicsConfig) {
+    ball.rally_hits += 1;
+    ball.speed = physics.rally_speed(ball.rally_hits);
+}
+
+/// Finds the first contact between a circle moving from `start` to `end` and the box spanning
+/// `min` to `max`.
+///
+/// Returns `None` if the circle misses the box, or if it already overlaps the box at `start`.
+pub fn sweep_circle_aabb(
+    start: [f32; 2],
+    end: [f32; 2],
+    radius: f32,
+    min: [f32; 2],
+    max: [f32; 2],
+) -> Option<Hit> {
+    let delta = sub(end, start);
+
+    // Sweep the centre as a ray against the box grown by the radius on every side (slab test).
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    let mut normal = [0.0, 0.0];
+    for axis in 0..2 {
+        let low = min[axis] - radius;
+        let high = max[axis] + radius;
+        if delta[axis] == 0.0 {
+            if start[axis] < low || start[axis] > high {
+                return None;
+            }
+            continue;
+        }
+        let t_low = (low - start[axis]) / delta[axis];
+        let t_high = (high - start[axis]) / delta[axis];
+        let (near, far) = if t_low < t_high { (t_low, t_high) } else { (t_high, t_low) };
+        if near > t_enter {
+            t_enter = near;
+            normal = [0.0, 0.0];
+            normal[axis] = if delta[axis] > 0.0 { -1.0 } else { 1.0 };
+        }
+        t_exit = t_exit.min(far);
+    }
+
+    if t_enter > t_exit || !(0.0..=1.0).contains(&t_enter) {
+        return None;
+    }
+
+    let point = [start[0] + delta[0] * t_enter, start[1] + delta[1] * t_enter];
+
+    // The grown box has square corners but the real shape has rounded ones. If the ray entered
+    // through a corner region, redo the test against the circle around that corner.
+    let corner_x = if point[0] < min[0] {
+        min[0]
+    } else if point[0] > max[0] {
+        max[0]
+    } else {
+        return Some(Hit { time: t_enter, point, normal });
+    };
+    let corner_y = if point[1] < min[1] {
+        min[1]
+    } else if point[1] > max[1] {
+        max[1]
+    } else {
+        return Some(Hit { time: t_enter, point, normal });
+    };
+    sweep_point_circle(start, delta, [corner_x, corner_y], radius)
+}
+
+// Solves |start + delta * t - centre| = radius for the first t in 0..=1.
+fn sweep_point_circle(start: [f32; 2], delta: [f32; 2], centre: [f32; 2], radius: f32) -> Option<Hit> {
+    let offset = sub(start, centre);
+    let a = dot(delta, delta);
+    let b = 2.0 * dot(offset, delta);
+    let c = dot(offset, offset) - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let time = (-b - discriminant.sqrt()) / (2.0 * a);
+    if !(0.0..=1.0).contains(&time) {
+        return None;
+    }
+    let point = [start[0] + delta[0] * time, start[1] + delta[1] * time];
+    let normal = [(point[0] - centre[0]) / radius, (point[1] - centre[1]) / radius];
+    Some(Hit { time, point, normal })
+}
+
+// Mirrors a vector about the surface with the given unit normal.
+fn reflect(v: [f32; 2], normal: [f32; 2]) -> [f32; 2] {
+    let d = 2.0 * dot(v, normal);
+    [v[0] - d * normal[0], v[1] - d * normal[1]]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn length(v: [f32; 2]) -> f32 {
+    dot(v, v).sqrt()
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+// A point is in a box when its coordinates are smaller or equal than the top right and larger
+// or equal than the bottom left.
+fn point_in_rect(x: f32, y: f32, left: f32, bottom: f32, right: f32, top: f32) -> bool {
+    x >= left && x <= right && y >= bottom && y <= top
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameConfig;
+
+    fn ball_at(position: [f32; 2], direction: [f32; 2], speed: f32) -> Ball {
+        let mut ball = Ball::new(&GameConfig::default(), speed);
+        ball.position = position;
+        ball.previous_position = position;
+        ball.direction = direction;
+        ball
+    }
+
+    #[test]
+    fn sweep_finds_the_first_contact() {
+        let hit = sweep_circle_aabb([0.0, 0.0], [20.0, 0.0], 1.0, [9.0, -5.0], [11.0, 5.0]).unwrap();
+        assert!((hit.time - 0.4).abs() < 1e-6);
+        assert_eq!(hit.point, [8.0, 0.0]);
+        assert_eq!(hit.normal, [-1.0, 0.0]);
+    }
+
+    #[test]
+    fn sweep_misses_a_box_the_path_passes() {
+        assert_eq!(sweep_circle_aabb([0.0, 10.0], [20.0, 10.0], 1.0, [9.0, -5.0], [11.0, 5.0]), None);
+        // stops short of the box
+        assert_eq!(sweep_circle_aabb([0.0, 0.0], [7.0, 0.0], 1.0, [9.0, -5.0], [11.0, 5.0]), None);
+    }
+
+    #[test]
+    fn sweep_does_not_tunnel_at_high_speed() {
+        // a path thousands of times longer than the box is wide, both ends well clear of it
+        let hit = sweep_circle_aabb([-5000.0, 1.0], [5000.0, 1.0], 2.0, [-2.0, -8.0], [2.0, 8.0]).unwrap();
+        assert_eq!(hit.normal, [-1.0, 0.0]);
+        assert!((hit.point[0] + 4.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sweep_rounds_the_corners() {
+        // the path crosses the grown box's square corner but stays more than the radius away
+        // from the box's real corner at (-2, 8)
+        assert_eq!(sweep_circle_aabb([-3.9, 7.9], [-1.9, 9.9], 1.0, [-2.0, -8.0], [2.0, 8.0]), None);
+        // heading straight at the corner, it touches the rounded one
+        let hit = sweep_circle_aabb([-4.0, 10.2], [-1.0, 7.2], 1.0, [-2.0, -8.0], [2.0, 8.0]).unwrap();
+        assert!(hit.normal[0] < 0.0 && hit.normal[1] > 0.0);
+    }
+
+    #[test]
+    fn fast_ball_bounces_off_paddle_instead_of_passing_through() {
+        let config = GameConfig::default();
+        let physics = PhysicsConfig::default();
+        let paddle = Paddle::new(Side::Right, &config);
+        let mut ball = ball_at([50.0, 50.0], [1.0, 0.0], 10_000.0);
+        // one tick took the ball from the middle to far beyond the paddle
+        ball.position = [150.0, 50.0];
+
+        assert!(bounce_off_paddle(&mut ball, &paddle, &physics));
+        assert!(ball.direction[0] < 0.0);
+        assert!(ball.position[0] < paddle.position[0] - paddle.width * 0.5);
+    }
+
+    #[test]
+    fn face_hit_speeds_the_ball_up() {
+        let config = GameConfig::default();
+        let physics = PhysicsConfig::default();
+        let paddle = Paddle::new(Side::Left, &config);
+        let mut ball = ball_at([10.0, 50.0], [-1.0, 0.0], physics.ball_speed);
+        ball.position = [3.0, 50.0];
+
+        assert!(bounce_off_paddle(&mut ball, &paddle, &physics));
+        assert!(ball.direction[0] > 0.0);
+        assert_eq!(ball.rally_hits, 1);
+        assert_eq!(ball.speed, physics.rally_speed(1));
+    }
+
+    #[test]
+    fn edge_hit_is_not_a_return() {
+        let config = GameConfig::default();
+        let physics = PhysicsConfig::default();
+        let paddle = Paddle::new(Side::Left, &config);
+        // dropping onto the top of the paddle, which ends at y = 58
+        let mut ball = ball_at([2.0, 62.0], [0.0, -1.0], physics.ball_speed);
+        ball.position = [2.0, 59.0];
+
+        assert!(bounce_off_paddle(&mut ball, &paddle, &physics));
+        assert_eq!(ball.direction, [0.0, 1.0]);
+        assert_eq!(ball.rally_hits, 0);
+        assert_eq!(ball.speed, physics.ball_speed);
+        assert_eq!(ball.spin, 0.0);
+    }
+
+    #[test]
+    fn walls_reflect_the_ball_once() {
+        let mut ball = ball_at([50.0, 1.0], [0.6, -0.8], 86.0);
+        ball.spin = 10.0;
+
+        assert!(bounce_off_walls(&mut ball, 100.0));
+        assert_eq!(ball.position[1], 3.0);
+        assert_eq!(ball.direction, [0.6, 0.8]);
+        assert_eq!(ball.spin, -10.0);
+        // now heading away from the wall, so no second bounce
+        ball.position[1] = 1.5;
+        assert!(!bounce_off_walls(&mut ball, 100.0));
+    }
+
+    #[test]
+    fn top_wall_reflects_downwards() {
+        let mut ball = ball_at([50.0, 99.0], [0.6, 0.8], 86.0);
+        assert!(bounce_off_walls(&mut ball, 100.0));
+        assert_eq!(ball.position[1], 97.0);
+        assert_eq!(ball.direction, [0.6, -0.8]);
+    }
+}
@@ -0,0 +1,92 @@
+//! Settings loaded from the RON files in `config/`.
+
+use serde::{Deserialize, Serialize};
+
+/// Contents of `config/game.ron`: the size of the arena and of everything in it, in arena
+/// units. Missing fields fall back to their defaults.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub arena_width: f32,
+    pub arena_height: f32,
+    pub paddle_width: f32,
+    pub paddle_height: f32,
+    /// Paddle speed in arena units per second at full axis deflection.
+    pub paddle_speed: f32,
+    pub ball_radius: f32,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            arena_width: 100.0,
+            arena_height: 100.0,
+            paddle_width: 4.0,
+            paddle_height: 16.0,
+            paddle_speed: 72.0,
+            ball_radius: 2.0,
+        }
+    }
+}
+
+/// Contents of `config/audio.ron`. Volumes run from 0.0 (silent) to 1.0.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub music_volume: f32,
+    pub effects_volume: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> AudioConfig {
+        AudioConfig {
+            music_volume: 0.25,
+            effects_volume: 1.0,
+        }
+    }
+}
+
+/// Contents of `config/physics.ron`. Missing fields fall back to their defaults.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PhysicsConfig {
+    /// Physics ticks per second.
+    pub tick_rate: u32,
+    /// Steepest angle, in degrees from horizontal, a ball leaves a paddle at. Hits on the
+    /// paddle's centre go straight back and hits on its ends leave at this angle.
+    pub max_bounce_angle: f32,
+    /// Ball speed in arena units per second at the start of every rally.
+    pub ball_speed: f32,
+    /// Speed added to the ball each time a paddle returns it.
+    pub ball_speed_per_hit: f32,
+    /// The ball never gets faster than this, however long the rally.
+    pub max_ball_speed: f32,
+    /// Share of a paddle's vertical speed passed into the ball when it hits the paddle's face.
+    pub english: f32,
+    /// Spin, in degrees per second of curve, given to the ball per unit of paddle speed.
+    pub spin_per_paddle_speed: f32,
+    /// Fraction of the ball's spin lost per second.
+    pub spin_decay: f32,
+}
+
+impl PhysicsConfig {
+    /// Ball speed after `rally_hits` paddle returns.
+    pub fn rally_speed(&self, rally_hits: u32) -> f32 {
+        (self.ball_speed + self.ball_speed_per_hit * rally_hits as f32).min(self.max_ball_speed)
+    }
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> PhysicsConfig {
+        PhysicsConfig {
+            tick_rate: 120,
+            max_bounce_angle: 60.0,
+            ball_speed: 86.0,
+            ball_speed_per_hit: 6.0,
+            max_ball_speed: 200.0,
+            english: 0.3,
+            spin_per_paddle_speed: 0.5,
+            spin_decay: 1.5,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Sources of paddle movement.
+//!
+//! Every tick each paddle asks its `PaddleController` whether to serve and how far to move.
+//! Human controllers read devices through an `InputSource`, which the game implements on top of
+//! its engine's input handling; the others only look at the arena.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Ball, Paddle, ScoreBoard};
+
+// Within this many arena units of its target a paddle slows down instead of overshooting and
+// jittering around it.
+const SLOWDOWN_DISTANCE: f32 = 4.0;
+
+/// Raw device state for human controllers. Every method defaults to "no input".
+pub trait InputSource {
+    /// A named axis from the input bindings, in `-1.0..=1.0`.
+    fn axis(&self, _name: &str) -> Option<f32> {
+        None
+    }
+
+    /// The vertical stick of a gamepad, in `-1.0..=1.0` with up positive.
+    fn gamepad_axis(&self, _pad: u32) -> Option<f32> {
+        None
+    }
+
+    /// The pointer's height in arena coordinates.
+    fn pointer_y(&self) -> Option<f32> {
+        None
+    }
+
+    /// Whether a named action from the input bindings is held down.
+    fn action(&self, _name: &str) -> Option<bool> {
+        None
+    }
+}
+
+/// An `InputSource` with nothing plugged in, for headless runs and tools.
+pub struct NoInput;
+
+impl InputSource for NoInput {}
+
+/// What a controller gets to look at when deciding on a move.
+pub struct ControlContext<'a> {
+    pub paddle: &'a Paddle,
+    /// The other side's paddle as it was before this tick's moves, `None` if there isn't one.
+    pub opponent: Option<&'a Paddle>,
+    /// `None` if there is no ball. A ball waiting to be served is given, standing still in the
+    /// middle.
+    pub ball: Option<&'a Ball>,
+    pub arena_height: f32,
+    pub scores: &'a ScoreBoard,
+    /// Length of the tick in seconds.
+    pub dt: f32,
+    pub input: &'a dyn InputSource,
+}
+
+/// Supplies a paddle's desired movement each tick.
+pub trait PaddleController: Send + Sync {
+    /// The axis value to move the paddle by this tick, in `-1.0..=1.0` with up positive.
+    fn movement(&mut self, context: &ControlContext<'_>) -> f32;
+
+    /// Whether to serve the ball this tick, if it is this side's serve. Asked every tick just
+    /// before `movement`. Controllers that never press serve leave it to the countdown.
+    fn serve(&mut self, _context: &ControlContext<'_>) -> bool {
+        false
+    }
+}
+
+/// Full speed towards `target_y`, easing off when close.
+pub fn steer_towards(paddle: &Paddle, target_y: f32) -> f32 {
+    ((target_y - paddle.position[1]) / SLOWDOWN_DISTANCE).clamp(-1.0, 1.0)
+}
+
+/// Follows a named input axis, e.g. a pair of keys, and optionally serves on a named action.
+pub struct AxisController {
+    axis: String,
+    serve_action: Option<String>,
+}
+
+impl AxisController {
+    pub fn new<S: Into<String>>(axis: S) -> AxisController {
+        AxisController {
+            axis: axis.into(),
+            serve_action: None,
+        }
+    }
+
+    pub fn with_serve_action<S: Into<String>>(mut self, action: S) -> AxisController {
+        self.serve_action = Some(action.into());
+        self
+    }
+}
+
+impl PaddleController for AxisController {
+    fn movement(&mut self, context: &ControlContext<'_>) -> f32 {
+        context.input.axis(&self.axis).unwrap_or(0.0)
+    }
+
+    fn serve(&mut self, context: &ControlContext<'_>) -> bool {
+        match self.serve_action {
+            Some(ref action) => context.input.action(action).unwrap_or(false),
+            None => false,
+        }
+    }
+}
+
+/// Follows the vertical stick of a gamepad.
+pub struct GamepadController {
+    pad: u32,
+}
+
+impl GamepadController {
+    pub fn new(pad: u32) -> GamepadController {
+        GamepadController { pad }
+    }
+}
+
+impl PaddleController for GamepadController {
+    fn movement(&mut self, context: &ControlContext<'_>) -> f32 {
+        context.input.gamepad_axis(self.pad).unwrap_or(0.0)
+    }
+}
+
+/// Moves the paddle towards the pointer's height.
+pub struct PointerController;
+
+impl PaddleController for PointerController {
+    fn movement(&mut self, context: &ControlContext<'_>) -> f32 {
+        match context.input.pointer_y() {
+            Some(y) => steer_towards(context.paddle, y),
+            None => 0.0,
+        }
+    }
+}
+
+/// One entry of a movement script: hold `movement` for `seconds`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScriptStep {
+    pub seconds: f32,
+    pub movement: f32,
+}
+
+/// Plays a fixed list of moves and then starts over.
+pub struct ScriptedController {
+    steps: Vec<ScriptStep>,
+    index: usize,
+    elapsed: f32,
+}
+
+impl ScriptedController {
+    pub fn new(steps: Vec<ScriptStep>) -> ScriptedController {
+        ScriptedController {
+            steps,
+            index: 0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl PaddleController for ScriptedController {
+    fn movement(&mut self, context: &ControlContext<'_>) -> f32 {
+        if self.steps.is_empty() {
+            return 0.0;
+        }
+        // skip past finished steps; the step count bounds the loop if every step is empty
+        for _ in 0..self.steps.len() {
+            if self.elapsed < self.steps[self.index].seconds {
+                break;
+            }
+            self.elapsed -= self.steps[self.index].seconds;
+            self.index = (self.index + 1) % self.steps.len();
+        }
+        self.elapsed += context.dt;
+        self.steps[self.index].movement
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Arena, Event, Input, Side};
+
+    const DT: f32 = 1.0 / 120.0;
+
+    // climbs as fast as it can and serves straight away
+    struct Climber;
+
+    impl PaddleController for Climber {
+        fn movement(&mut self, _context: &ControlContext<'_>) -> f32 {
+            1.0
+        }
+
+        fn serve(&mut self, _context: &ControlContext<'_>) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn arena_contexts_show_both_paddles_and_the_waiting_ball() {
+        let arena = Arena::default();
+        assert!(arena.serve.is_waiting());
+        for side in [Side::Left, Side::Right] {
+            let context = arena.control_context(side, DT, &NoInput);
+            assert!(std::ptr::eq(context.paddle, arena.paddle(side)));
+            assert!(context.opponent.is_some_and(|opponent| std::ptr::eq(opponent, arena.paddle(side.other()))));
+            let ball = context.ball.expect("the waiting ball");
+            assert!(std::ptr::eq(ball, &arena.ball));
+            assert_eq!(ball.velocity(), [0.0, 0.0]);
+            assert!(std::ptr::eq(context.scores, &arena.scores));
+            assert_eq!((context.arena_height, context.dt), (arena.height, DT));
+        }
+    }
+
+    #[test]
+    fn a_custom_controller_drives_the_arena() {
+        let mut arena = Arena::default().with_seed(2);
+        let start = arena.left.position[1];
+        let server = arena.serve.server;
+        let mut events = Vec::new();
+        for _ in 0..30 {
+            let context = arena.control_context(Side::Left, DT, &NoInput);
+            let input = Input {
+                left: Climber.movement(&context),
+                serve_left: Climber.serve(&context),
+                serve_right: true,
+                ..Input::default()
+            };
+            events.extend(arena.step(input, DT));
+        }
+        assert!(arena.left.position[1] > start);
+        assert_eq!(arena.right.position[1], start);
+        // serving straight away beats the countdown
+        assert_eq!(events.first(), Some(&Event::Served(server)));
+    }
+}
@@ -0,0 +1,343 @@
+//! A reinforcement learning environment in the style of OpenAI Gym.
+//!
+//! `Env` plays a match with `Arena::step`, so agents train against the very rules the game
+//! runs, not a copy of them. Each `step` takes a paddle movement for both sides and returns the
+//! observation, the reward and whether the match is over. `Request` and `Response` are the
+//! messages `pong-gym` exchanges with a trainer, one JSON object per line.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Arena, Event, FixedTimestep, Input, Side};
+
+/// Length of an observation: the ball's position and velocity, then the left and right
+/// paddles' heights, all in arena units from the bottom left.
+pub const OBSERVATION_SIZE: usize = 6;
+
+/// `[ball x, ball y, ball velocity x, ball velocity y, left paddle y, right paddle y]`. The
+/// paddles only move up and down, so their `x` never changes.
+pub type Observation = [f32; OBSERVATION_SIZE];
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EnvSettings {
+    /// Physics ticks played per `step`, with the same actions.
+    pub ticks_per_step: u32,
+    /// Ticks after which a match ends even without a winner.
+    pub max_ticks: Option<u32>,
+    /// Serves as soon as the ball is held, instead of waiting out the serve countdown.
+    pub auto_serve: bool,
+}
+
+impl Default for EnvSettings {
+    fn default() -> EnvSettings {
+        EnvSettings {
+            ticks_per_step: 1,
+            max_ticks: None,
+            auto_serve: true,
+        }
+    }
+}
+
+/// What a `step` led to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Step {
+    pub observation: Observation,
+    /// +1.0 for every point the left side won during the step and -1.0 for every point the
+    /// right side won. The right side's reward is the negative of this.
+    pub reward: f32,
+    /// The match is over, or ran into `max_ticks`; call `reset` to play another.
+    pub done: bool,
+}
+
+/// A match for two agents, one on each paddle.
+pub struct Env {
+    // the match as it is before the first tick, copied on every reset
+    start: Arena,
+    arena: Arena,
+    settings: EnvSettings,
+    dt: f32,
+    tick: u32,
+}
+
+impl Env {
+    /// Plays matches in arenas like `arena`, which should not have been stepped yet.
+    pub fn new(arena: Arena, settings: EnvSettings) -> Env {
+        let dt = FixedTimestep::new(arena.physics.tick_rate).tick_seconds();
+        Env {
+            start: arena.clone(),
+            arena,
+            settings,
+            dt,
+            tick: 0,
+        }
+    }
+
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    /// Ticks played since the last reset.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Starts a new match whose serves are drawn from `seed`, and returns its first
+    /// observation.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.arena = self.start.clone().with_seed(seed);
+        self.tick = 0;
+        self.observation()
+    }
+
+    /// Plays `ticks_per_step` ticks with the left paddle moving by `left` and the right one
+    /// by `right`, each between -1.0 (down) and 1.0 (up) like the paddle axes.
+    pub fn step(&mut self, left: f32, right: f32) -> Step {
+        let input = Input {
+            left: clamp_action(left),
+            right: clamp_action(right),
+            serve_left: self.settings.auto_serve,
+            serve_right: self.settings.auto_serve,
+        };
+        let mut reward = 0.0;
+        for _ in 0..self.settings.ticks_per_step.max(1) {
+            if self.is_done() {
+                break;
+            }
+            for event in self.arena.step(input, self.dt) {
+                match event {
+                    Event::Point(Side::Left) => reward += 1.0,
+                    Event::Point(Side::Right) => reward -= 1.0,
+                    _ => {}
+                }
+            }
+            self.tick += 1;
+        }
+        Step {
+            observation: self.observation(),
+            reward,
+            done: self.is_done(),
+        }
+    }
+
+    pub fn observation(&self) -> Observation {
+        let ball = &self.arena.ball;
+        let velocity = ball.velocity();
+        [
+            ball.position[0],
+            ball.position[1],
+            velocity[0],
+            velocity[1],
+            self.arena.left.position[1],
+            self.arena.right.position[1],
+        ]
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.arena.state.is_over() || self.settings.max_ticks.is_some_and(|max| self.tick >= max)
+    }
+
+    /// Answers one request of the line protocol.
+    pub fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::Reset { seed } => Response::Reset {
+                observation: self.reset(seed),
+            },
+            Request::Step { left, right } => {
+                let step = self.step(left, right);
+                Response::Step {
+                    observation: step.observation,
+                    reward: step.reward,
+                    done: step.done,
+                    info: self.info(),
+                }
+            }
+        }
+    }
+
+    fn info(&self) -> Info {
+        Info {
+            tick: self.tick,
+            score_left: self.arena.scores.score_left,
+            score_right: self.arena.scores.score_right,
+            winner: self.arena.state.winner,
+        }
+    }
+}
+
+// a NaN action would poison the paddle's position for the rest of the match
+fn clamp_action(action: f32) -> f32 {
+    if action.is_finite() {
+        action.clamp(-1.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// A line from the trainer, e.g. `{"cmd": "reset", "seed": 7}` or
+/// `{"cmd": "step", "left": 1.0, "right": -0.5}`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Reset {
+        #[serde(default)]
+        seed: u64,
+    },
+    Step {
+        left: f32,
+        right: f32,
+    },
+}
+
+/// The line sent back for each request, e.g. `{"observation": [...]}` after a reset.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Response {
+    Step {
+        observation: Observation,
+        reward: f32,
+        done: bool,
+        info: Info,
+    },
+    Reset {
+        observation: Observation,
+    },
+    /// The request could not be read.
+    Error {
+        error: String,
+    },
+}
+
+/// More about the match after a step, for logging rather than learning.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Info {
+    pub tick: u32,
+    pub score_left: i32,
+    pub score_right: i32,
+    pub winner: Option<Side>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(settings: EnvSettings) -> Env {
+        Env::new(Arena::default(), settings)
+    }
+
+    // both paddles chase the ball at different speeds, so points are won and lost
+    fn play(env: &mut Env, steps: u32) -> (Vec<Observation>, f32) {
+        let mut observations = Vec::new();
+        let mut total = 0.0;
+        for _ in 0..steps {
+            let ball_y = env.arena().ball.position[1];
+            let left = (ball_y - env.arena().left.position[1]) / 4.0;
+            let right = (ball_y - env.arena().right.position[1]) / 20.0;
+            let step = env.step(left, right);
+            observations.push(step.observation);
+            total += step.reward;
+        }
+        (observations, total)
+    }
+
+    #[test]
+    fn reset_is_deterministic_for_a_seed() {
+        let mut env = env(EnvSettings::default());
+        let first = env.reset(7);
+        let (first_run, _) = play(&mut env, 2000);
+        assert_eq!(env.reset(7), first);
+        assert_eq!(env.tick(), 0);
+        assert_eq!(play(&mut env, 2000).0, first_run);
+
+        env.reset(8);
+        assert_ne!(play(&mut env, 2000).0, first_run);
+    }
+
+    #[test]
+    fn rewards_add_up_across_ticks_per_step() {
+        let mut single = env(EnvSettings::default());
+        let mut batched = env(EnvSettings {
+            ticks_per_step: 10,
+            ..EnvSettings::default()
+        });
+        single.reset(3);
+        batched.reset(3);
+        let mut single_total = 0.0;
+        let mut batched_total = 0.0;
+        for _ in 0..500 {
+            // a paddle that never moves lets points through
+            batched_total += batched.step(0.0, 1.0).reward;
+            for _ in 0..10 {
+                single_total += single.step(0.0, 1.0).reward;
+            }
+        }
+        let scores = &batched.arena().scores;
+        assert!(scores.score_left + scores.score_right > 0, "no points were played");
+        assert_eq!(batched_total, (scores.score_left - scores.score_right) as f32);
+        assert_eq!(batched_total, single_total);
+        assert_eq!(batched.observation(), single.observation());
+    }
+
+    #[test]
+    fn done_trips_at_max_ticks() {
+        let mut env = env(EnvSettings {
+            ticks_per_step: 10,
+            max_ticks: Some(25),
+            ..EnvSettings::default()
+        });
+        env.reset(1);
+        assert!(!env.step(0.0, 0.0).done);
+        assert!(!env.step(0.0, 0.0).done);
+        assert!(env.step(0.0, 0.0).done);
+        assert_eq!(env.tick(), 25);
+        // a finished match doesn't move on
+        let observation = env.observation();
+        let step = env.step(1.0, 1.0);
+        assert!(step.done);
+        assert_eq!((env.tick(), step.observation), (25, observation));
+    }
+
+    #[test]
+    fn broken_actions_do_not_move_the_paddles() {
+        let mut env = env(EnvSettings::default());
+        let start = env.reset(1);
+        for _ in 0..10 {
+            env.step(f32::NAN, f32::INFINITY);
+        }
+        let observation = env.observation();
+        assert_eq!((observation[4], observation[5]), (start[4], start[5]));
+        env.step(1e9, -1e9);
+        let paddles = [env.arena().left.position[1], env.arena().right.position[1]];
+        assert!(paddles[0] > start[4] && paddles[1] < start[5]);
+        assert!(paddles.iter().all(|y| y.is_finite()));
+    }
+
+    #[test]
+    fn requests_are_read_from_json() {
+        let reset: Request = serde_json::from_str(r#"{"cmd": "reset", "seed": 7}"#).unwrap();
+        assert!(matches!(reset, Request::Reset { seed: 7 }));
+        let reset: Request = serde_json::from_str(r#"{"cmd": "reset"}"#).unwrap();
+        assert!(matches!(reset, Request::Reset { seed: 0 }));
+        let step: Request = serde_json::from_str(r#"{"cmd": "step", "left": 1.0, "right": -0.5}"#).unwrap();
+        assert!(matches!(step, Request::Step { left, right } if left == 1.0 && right == -0.5));
+        assert!(serde_json::from_str::<Request>(r#"{"cmd": "jump"}"#).is_err());
+    }
+
+    #[test]
+    fn responses_round_trip_through_json() {
+        let mut env = env(EnvSettings::default());
+        let responses = vec![
+            env.handle(Request::Reset { seed: 2 }),
+            env.handle(Request::Step { left: 0.5, right: -0.5 }),
+            Response::Error {
+                error: "expected value".to_string(),
+            },
+        ];
+        for response in responses {
+            let json = serde_json::to_string(&response).unwrap();
+            assert!(!json.contains("Reset") && !json.contains("Step"), "{}", json);
+            let read: Response = serde_json::from_str(&json).unwrap();
+            assert_eq!(std::mem::discriminant(&read), std::mem::discriminant(&response));
+            assert_eq!(serde_json::to_string(&read).unwrap(), json);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Game rules and physics for pong, with no dependency on a game engine.
+//!
+//! The amethyst systems in the `pong` binary are thin wrappers around the functions in this
+//! crate, and `Arena::step` runs the very same functions in the same order so a match can be
+//! simulated without a `World`.
+
+pub use self::ai::{AiController, Difficulty};
+pub use self::arena::{Arena, Event, Input};
+pub use self::ball::Ball;
+pub use self::config::{AudioConfig, GameConfig, PhysicsConfig};
+pub use self::controller::{ControlContext, InputSource, PaddleController};
+pub use self::paddle::{Paddle, Side};
+pub use self::replay::{Replay, ReplayController};
+pub use self::rng::Rng;
+pub use self::rules::{MatchRules, MatchState};
+pub use self::score::ScoreBoard;
+pub use self::serve::{Serve, ServeRule};
+pub use self::timestep::FixedTimestep;
+
+pub mod ai;
+pub mod bot;
+pub mod bounce;
+pub mod config;
+pub mod controller;
+pub mod gym;
+pub mod net;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod rules;
+pub mod score;
+pub mod serve;
+pub mod timestep;
+pub mod tournament;
+
+mod arena;
+mod ball;
+mod hash;
+mod paddle;
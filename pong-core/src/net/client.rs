@@ -0,0 +1,360 @@
+//! Playing in a match run by a `MatchServer`.
+//!
+//! The server's snapshots arrive a round trip after the input that caused them, which is far too
+//! late for the paddle under the player's hand. The client moves its own paddle straight away and
+//! corrects it whenever a snapshot says where the server had it; since paddles only depend on
+//! their own input, the correction is almost always nothing. The ball and the other paddle are
+//! drawn a few ticks in the past, between the two snapshots either side, so they move smoothly
+//! even when snapshots arrive unevenly or go missing.
+//!
+//! A spectating session draws both paddles that way and never sends inputs.
+
+use std::collections::VecDeque;
+
+use crate::net::protocol::MAX_INPUTS_PER_DATAGRAM;
+use crate::net::{Message, NetError, SideInput, Snapshot, Transport};
+use crate::timestep::lerp;
+use crate::{Arena, Event, Paddle, Side};
+
+// a spectator reminds the server it is still watching every this many ticks
+const WATCH_INTERVAL: u32 = 30;
+
+#[derive(Clone, Debug)]
+pub struct ClientSettings {
+    /// How many ticks behind the newest snapshot the ball and the other paddle are drawn. More
+    /// delay rides out more lost snapshots, at the cost of seeing the ball later.
+    pub interpolation_delay: u32,
+}
+
+impl Default for ClientSettings {
+    fn default() -> ClientSettings {
+        ClientSettings { interpolation_delay: 6 }
+    }
+}
+
+/// One player's or spectator's connection to a server match.
+pub struct ClientSession<T> {
+    transport: T,
+    config_hash: u64,
+    settings: ClientSettings,
+    spectating: bool,
+    /// The paddle the server gave us, once it has answered.
+    side: Option<Side>,
+    /// The match as it is drawn.
+    arena: Arena,
+    /// Our paddle as predicted from our own inputs.
+    predicted: Paddle,
+    inputs: Vec<SideInput>,
+    /// Inputs from 0 the server has used.
+    acked: u32,
+    /// Snapshots not yet drawn past, oldest first.
+    snapshots: VecDeque<Snapshot>,
+    /// The server tick being drawn.
+    render_tick: f32,
+    bounces: u32,
+    corrections: u32,
+    ticks: u32,
+}
+
+impl<T: Transport> ClientSession<T> {
+    /// Starts joining the server at the other end of `transport`. `arena` has to be set up
+    /// with the server's configuration, which `config_hash` identifies.
+    pub fn new(transport: T, arena: Arena, config_hash: u64, settings: ClientSettings) -> ClientSession<T> {
+        let predicted = arena.left.clone();
+        ClientSession {
+            transport,
+            config_hash,
+            settings,
+            spectating: false,
+            side: None,
+            arena,
+            predicted,
+            inputs: Vec::new(),
+            acked: 0,
+            snapshots: VecDeque::new(),
+            render_tick: 0.0,
+            bounces: 0,
+            corrections: 0,
+            ticks: 0,
+        }
+    }
+
+    /// Starts watching the match at the other end of `transport`, without a paddle.
+    pub fn spectate(transport: T, arena: Arena, config_hash: u64, settings: ClientSettings) -> ClientSession<T> {
+        ClientSession {
+            spectating: true,
+            ..ClientSession::new(transport, arena, config_hash, settings)
+        }
+    }
+
+    /// The match as it should be drawn.
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    /// The paddle this client plays, once the server has said.
+    pub fn local_side(&self) -> Option<Side> {
+        self.side
+    }
+
+    pub fn is_spectating(&self) -> bool {
+        self.spectating
+    }
+
+    /// The server has taken us in and is playing the match.
+    pub fn is_connected(&self) -> bool {
+        (self.side.is_some() || self.spectating) && self.snapshots.back().is_some_and(|snapshot| !snapshot.waiting)
+    }
+
+    /// How many of our inputs the server hasn't used yet, which is how far ahead of it our
+    /// paddle is predicted.
+    pub fn prediction_depth(&self) -> u32 {
+        self.inputs.len() as u32 - self.acked.min(self.inputs.len() as u32)
+    }
+
+    /// How many times a snapshot has put our paddle somewhere other than where we predicted.
+    pub fn corrections(&self) -> u32 {
+        self.corrections
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Sends our input for the next tick and moves the drawn match on by one tick. Returns the
+    /// events seen, or `None` while the server is waiting for players. A spectator's input is
+    /// ignored.
+    pub fn advance(&mut self, local: SideInput, dt: f32) -> Result<Option<Vec<Event>>, NetError> {
+        self.receive(dt)?;
+        self.ticks += 1;
+        if self.spectating {
+            if self.ticks % WATCH_INTERVAL == 1 {
+                let watch = Message::Watch {
+                    config_hash: self.config_hash,
+                };
+                self.transport.send(&watch.encode())?;
+            }
+            let events = self.show(None);
+            return Ok(if self.is_connected() { Some(events) } else { None });
+        }
+
+        let side = match self.side {
+            Some(side) => side,
+            None => {
+                let join = Message::Join {
+                    config_hash: self.config_hash,
+                };
+                self.transport.send(&join.encode())?;
+                return Ok(None);
+            }
+        };
+
+        // while the server waits for the other player the empty inputs keep our paddle ours
+        let connected = self.is_connected();
+        if connected {
+            self.inputs.push(local);
+            self.predicted.apply_input(local.movement, self.arena.height, dt);
+        }
+        self.send_inputs()?;
+        let events = self.show(Some(side));
+        Ok(if connected { Some(events) } else { None })
+    }
+
+    fn receive(&mut self, dt: f32) -> Result<(), NetError> {
+        while let Some(datagram) = self.transport.recv()? {
+            match Message::decode(&datagram) {
+                Some(Message::Welcome { side }) if self.side.is_none() => {
+                    self.side = Some(side);
+                    self.predicted = self.arena.paddle(side).clone();
+                }
+                Some(Message::Refused) if self.side.is_none() => return Err(NetError::Refused),
+                Some(Message::Snapshot(snapshot)) if self.side.is_some() || self.spectating => {
+                    self.receive_snapshot(snapshot, dt)
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn receive_snapshot(&mut self, snapshot: Snapshot, dt: f32) {
+        // late snapshots are of no use once a newer one is in
+        if self.snapshots.back().is_some_and(|newest| newest.tick > snapshot.tick) {
+            return;
+        }
+        if snapshot.ack >= self.acked && !self.spectating {
+            self.reconcile(&snapshot, dt);
+        }
+        if self.snapshots.back().is_some_and(|newest| newest.tick == snapshot.tick) {
+            self.snapshots.pop_back();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    // starts our paddle from where the server had it and replays the inputs it hadn't used yet
+    fn reconcile(&mut self, snapshot: &Snapshot, dt: f32) {
+        let side = match self.side {
+            Some(side) => side,
+            None => return,
+        };
+        self.acked = snapshot.ack;
+        let predicted_y = self.predicted.position[1];
+        let y = match side {
+            Side::Left => snapshot.left,
+            Side::Right => snapshot.right,
+        };
+        self.predicted.position[1] = y;
+        self.predicted.previous_position[1] = y;
+        let first = (self.acked as usize).min(self.inputs.len());
+        for input in &self.inputs[first..] {
+            self.predicted.apply_input(input.movement, self.arena.height, dt);
+        }
+        if (self.predicted.position[1] - predicted_y).abs() > 0.01 {
+            self.corrections += 1;
+        }
+    }
+
+    fn send_inputs(&mut self) -> Result<(), NetError> {
+        let first = (self.acked as usize).min(self.inputs.len());
+        let last = (first + MAX_INPUTS_PER_DATAGRAM).min(self.inputs.len());
+        let message = Message::ClientInputs {
+            first_sequence: first as u32,
+            inputs: self.inputs[first..last].to_vec(),
+        };
+        self.transport.send(&message.encode())?;
+        Ok(())
+    }
+
+    // moves the drawn match to the next tick and reports what changed; `side` is the paddle we
+    // predict ourselves, if any
+    fn show(&mut self, side: Option<Side>) -> Vec<Event> {
+        let mut events = Vec::new();
+        let (newest, stopped) = match self.snapshots.back() {
+            Some(newest) => (newest.tick as f32, newest.waiting || newest.winner.is_some()),
+            None => return events,
+        };
+
+        // stay `interpolation_delay` ticks behind the newest snapshot, easing back towards that
+        // when snapshots come early or late. Once the match stops no newer ones will come, so
+        // catch up with it.
+        let delay = self.settings.interpolation_delay as f32;
+        let target = if stopped { newest } else { (newest - delay).max(0.0) };
+        self.render_tick += 1.0;
+        if (self.render_tick - target).abs() > delay.max(1.0) {
+            self.render_tick = target;
+        } else {
+            self.render_tick += (target - self.render_tick) * 0.05;
+        }
+        self.render_tick = self.render_tick.min(newest);
+
+        while self.snapshots.len() > 1 && self.snapshots[1].tick as f32 <= self.render_tick {
+            self.snapshots.pop_front();
+        }
+        let from = self.snapshots[0].clone();
+        let to = self.snapshots.get(1).unwrap_or(&from).clone();
+        let alpha = if to.tick > from.tick {
+            ((self.render_tick - from.tick as f32) / (to.tick - from.tick) as f32).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        // a point puts the ball back in the middle; it doesn't travel there
+        let teleported = to.score_left != from.score_left || to.score_right != from.score_right;
+        let ball = if teleported { from.ball } else { lerp(from.ball, to.ball, alpha) };
+        self.arena.ball.previous_position = self.arena.ball.position;
+        self.arena.ball.position = ball;
+        if from.countdown.is_some() {
+            self.arena.ball.previous_position = ball;
+        }
+
+        for paddle in [&mut self.arena.left, &mut self.arena.right].iter_mut() {
+            paddle.previous_position = paddle.position;
+            if Some(paddle.side) == side {
+                paddle.position[1] = self.predicted.position[1];
+                paddle.velocity = self.predicted.velocity;
+            } else {
+                let y = |snapshot: &Snapshot| match paddle.side {
+                    Side::Left => snapshot.left,
+                    Side::Right => snapshot.right,
+                };
+                paddle.position[1] = y(&from) + (y(&to) - y(&from)) * alpha;
+            }
+        }
+
+        if from.bounces > self.bounces {
+            events.push(Event::Bounce);
+        }
+        self.bounces = self.bounces.max(from.bounces);
+        if self.arena.serve.countdown.is_some() && from.countdown.is_none() {
+            events.push(Event::Served(self.arena.serve.server));
+        }
+        if from.score_left > self.arena.scores.score_left {
+            events.push(Event::Point(Side::Left));
+        }
+        if from.score_right > self.arena.scores.score_right {
+            events.push(Event::Point(Side::Right));
+        }
+        if let (None, Some(winner)) = (self.arena.state.winner, from.winner) {
+            events.push(Event::Won(winner));
+        }
+
+        self.arena.scores.score_left = from.score_left;
+        self.arena.scores.score_right = from.score_right;
+        self.arena.serve.server = from.server;
+        self.arena.serve.countdown = from.countdown;
+        self.arena.state.winner = from.winner;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{MatchServer, ServerSettings, UdpTransport};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn connect(server: &MatchServer) -> UdpTransport {
+        UdpTransport::new("127.0.0.1:0", server.local_addr().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn players_predict_their_paddles_and_spectators_follow() {
+        let mut server = MatchServer::bind("127.0.0.1:0", Arena::default(), 7, ServerSettings::default()).unwrap();
+        let mut left = ClientSession::new(connect(&server), Arena::default(), 7, ClientSettings::default());
+        let mut right = ClientSession::new(connect(&server), Arena::default(), 7, ClientSettings::default());
+        let mut spectator = ClientSession::spectate(connect(&server), Arena::default(), 7, ClientSettings::default());
+
+        let up = SideInput {
+            movement: 1.0,
+            serve: false,
+        };
+        for tick in 0..120 {
+            // up for a while, then still, so the server catches up with the predictions
+            let input = if tick < 60 { up } else { SideInput::default() };
+            left.advance(input, DT).unwrap();
+            right.advance(SideInput::default(), DT).unwrap();
+            spectator.advance(SideInput::default(), DT).unwrap();
+            server.update(DT).unwrap();
+        }
+
+        assert_eq!(left.local_side(), Some(Side::Left));
+        assert_eq!(right.local_side(), Some(Side::Right));
+        assert!(left.is_connected() && right.is_connected() && spectator.is_connected());
+        assert!(spectator.is_spectating() && spectator.local_side().is_none());
+
+        let server_y = server.arena().left.position[1];
+        assert!(server_y > Arena::default().left.position[1]);
+        assert_eq!(left.arena().left.position[1], server_y);
+        assert_eq!(left.corrections(), 0);
+        // only the input sent this tick waits for the server's answer
+        assert_eq!(left.prediction_depth(), 1);
+        // the others draw the left paddle from snapshots, a few ticks behind
+        assert!((right.arena().left.position[1] - server_y).abs() < 0.01);
+        assert!((spectator.arena().left.position[1] - server_y).abs() < 0.01);
+    }
+}
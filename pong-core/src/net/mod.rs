@@ -0,0 +1,21 @@
+//! Networked matches.
+//!
+//! Peers exchange small UDP datagrams in the format of `protocol`. In a peer-to-peer match each
+//! side runs the whole simulation and only sends its own inputs; `RollbackSession` predicts the
+//! other side's inputs and re-simulates when they turn out to be wrong. In a server match only
+//! the `MatchServer` simulates, and each `ClientSession` draws the snapshots it sends.
+//! `SimulatedTransport` makes any of them play over a link with delays and losses.
+
+pub use self::client::{ClientSession, ClientSettings};
+pub use self::protocol::{Message, SideInput, Snapshot};
+pub use self::rollback::{NetError, RollbackSession, RollbackSettings};
+pub use self::server::{MatchServer, ServerSettings};
+pub use self::sim::{LinkConditions, Phase, Scenario, SimulatedTransport};
+pub use self::transport::{Transport, UdpTransport};
+
+pub mod client;
+pub mod protocol;
+pub mod rollback;
+pub mod server;
+pub mod sim;
+pub mod transport;
@@ -0,0 +1,460 @@
+//! The datagrams peers exchange, and those between `pong-server` and its clients.
+//!
+//! Every datagram starts with the bytes `PN`, the protocol version and a message tag, followed by
+//! the message's fields in little-endian order. Anything that doesn't parse is ignored.
+
+use crate::Side;
+
+const MAGIC: &[u8; 2] = b"PN";
+/// Protocol version sent with every datagram. Datagrams with any other version are ignored.
+pub const VERSION: u8 = 1;
+
+// most inputs sent in one datagram
+pub(crate) const MAX_INPUTS_PER_DATAGRAM: usize = 64;
+
+const HELLO: u8 = 0;
+const INPUTS: u8 = 1;
+const JOIN: u8 = 2;
+const WELCOME: u8 = 3;
+const REFUSED: u8 = 4;
+const CLIENT_INPUTS: u8 = 5;
+const SNAPSHOT: u8 = 6;
+const WATCH: u8 = 7;
+
+/// One side's input for one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SideInput {
+    /// Paddle movement in `-1.0..=1.0`.
+    pub movement: f32,
+    pub serve: bool,
+}
+
+// a peer or client is only trusted to say which way it wants to move, not how fast
+pub(crate) fn sanitize(input: SideInput) -> SideInput {
+    let movement = if input.movement.is_finite() { input.movement.clamp(-1.0, 1.0) } else { 0.0 };
+    SideInput { movement, ..input }
+}
+
+/// What a server match looks like after `tick` ticks, as sent to one of its clients.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    /// How many of the receiving client's inputs the server has used so far, 0 for spectators.
+    pub ack: u32,
+    /// The server is waiting for a player and the match is paused.
+    pub waiting: bool,
+    pub ball: [f32; 2],
+    /// Height of the left and right paddles.
+    pub left: f32,
+    pub right: f32,
+    pub score_left: i32,
+    pub score_right: i32,
+    /// Bounces off paddles and walls since the match started, so clients can tell a bounce
+    /// happened even if the snapshot right after it was lost.
+    pub bounces: u32,
+    pub server: Side,
+    /// Seconds until the ball is served, while it is held.
+    pub countdown: Option<f32>,
+    pub winner: Option<Side>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// Sent until the peer answers, to agree on how the match is played. `side` is the paddle
+    /// the sender plays, and the left side's seed is used for the serves.
+    Hello { seed: u64, config_hash: u64, side: Side },
+    /// The sender's inputs for `first_frame` onwards.
+    Inputs {
+        /// How many of the receiver's frames the sender has, counting from frame 0 without
+        /// gaps. The receiver doesn't need to send those again.
+        ack: u32,
+        first_frame: u32,
+        inputs: Vec<SideInput>,
+        /// The sender's checksum of its arena after this many frames, if it has one.
+        checksum: Option<(u32, u64)>,
+    },
+    /// Sent by a client until the server answers, asking for a paddle.
+    Join { config_hash: u64 },
+    /// The server's answer to `Join`: the client plays `side`.
+    Welcome { side: Side },
+    /// The server's answer to `Join` when both paddles are taken or the configurations differ.
+    Refused,
+    /// A client's inputs, numbered from 0, for `first_sequence` onwards.
+    ClientInputs { first_sequence: u32, inputs: Vec<SideInput> },
+    Snapshot(Snapshot),
+    /// Sent by a spectator every so often, to start and keep getting snapshots.
+    Watch { config_hash: u64 },
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Writer(MAGIC.to_vec());
+        out.u8(VERSION);
+        match self {
+            Message::Hello { seed, config_hash, side } => {
+                out.u8(HELLO);
+                out.u64(*seed);
+                out.u64(*config_hash);
+                out.side(*side);
+            }
+            Message::Inputs { ack, first_frame, inputs, checksum } => {
+                out.u8(INPUTS);
+                out.u32(*ack);
+                out.u32(*first_frame);
+                out.u16(inputs.len() as u16);
+                for input in inputs {
+                    out.side_input(input);
+                }
+                match checksum {
+                    Some((frame, checksum)) => {
+                        out.u8(1);
+                        out.u32(*frame);
+                        out.u64(*checksum);
+                    }
+                    None => out.u8(0),
+                }
+            }
+            Message::Join { config_hash } => {
+                out.u8(JOIN);
+                out.u64(*config_hash);
+            }
+            Message::Welcome { side } => {
+                out.u8(WELCOME);
+                out.side(*side);
+            }
+            Message::Refused => out.u8(REFUSED),
+            Message::ClientInputs { first_sequence, inputs } => {
+                out.u8(CLIENT_INPUTS);
+                out.u32(*first_sequence);
+                out.u16(inputs.len() as u16);
+                for input in inputs {
+                    out.side_input(input);
+                }
+            }
+            Message::Snapshot(snapshot) => {
+                out.u8(SNAPSHOT);
+                out.snapshot(snapshot);
+            }
+            Message::Watch { config_hash } => {
+                out.u8(WATCH);
+                out.u64(*config_hash);
+            }
+        }
+        out.0
+    }
+
+    /// Parses a datagram, or returns `None` if it isn't a message of this protocol version.
+    pub fn decode(datagram: &[u8]) -> Option<Message> {
+        let mut input = Reader(datagram);
+        if input.take(2)? != MAGIC || input.u8()? != VERSION {
+            return None;
+        }
+        let message = match input.u8()? {
+            HELLO => Message::Hello {
+                seed: input.u64()?,
+                config_hash: input.u64()?,
+                side: input.side()?,
+            },
+            INPUTS => {
+                let ack = input.u32()?;
+                let first_frame = input.u32()?;
+                let count = input.u16()?;
+                let inputs = (0..count)
+                    .map(|_| input.side_input())
+                    .collect::<Option<Vec<_>>>()?;
+                let checksum = match input.u8()? {
+                    0 => None,
+                    _ => Some((input.u32()?, input.u64()?)),
+                };
+                Message::Inputs { ack, first_frame, inputs, checksum }
+            }
+            JOIN => Message::Join { config_hash: input.u64()? },
+            WELCOME => Message::Welcome { side: input.side()? },
+            REFUSED => Message::Refused,
+            CLIENT_INPUTS => {
+                let first_sequence = input.u32()?;
+                let count = input.u16()?;
+                let inputs = (0..count)
+                    .map(|_| input.side_input())
+                    .collect::<Option<Vec<_>>>()?;
+                Message::ClientInputs { first_sequence, inputs }
+            }
+            SNAPSHOT => Message::Snapshot(input.snapshot()?),
+            WATCH => Message::Watch { config_hash: input.u64()? },
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+pub(crate) struct Writer(pub(crate) Vec<u8>);
+
+impl Writer {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn f32(&mut self, value: f32) {
+        self.u32(value.to_bits());
+    }
+
+    pub(crate) fn side_input(&mut self, input: &SideInput) {
+        self.f32(input.movement);
+        self.u8(input.serve as u8);
+    }
+
+    pub(crate) fn side(&mut self, side: Side) {
+        self.u8(side as u8);
+    }
+
+    pub(crate) fn snapshot(&mut self, snapshot: &Snapshot) {
+        self.u32(snapshot.tick);
+        self.u32(snapshot.ack);
+        self.u8(snapshot.waiting as u8);
+        self.f32(snapshot.ball[0]);
+        self.f32(snapshot.ball[1]);
+        self.f32(snapshot.left);
+        self.f32(snapshot.right);
+        self.u32(snapshot.score_left as u32);
+        self.u32(snapshot.score_right as u32);
+        self.u32(snapshot.bounces);
+        self.side(snapshot.server);
+        self.f32(snapshot.countdown.unwrap_or(-1.0));
+        match snapshot.winner {
+            Some(side) => self.side(side),
+            None => self.u8(2),
+        }
+    }
+}
+
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        Some(u16::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Some(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_bits(self.u32()?))
+    }
+
+    pub(crate) fn side_input(&mut self) -> Option<SideInput> {
+        Some(SideInput {
+            movement: self.f32()?,
+            serve: self.u8()? != 0,
+        })
+    }
+
+    pub(crate) fn side(&mut self) -> Option<Side> {
+        match self.u8()? {
+            0 => Some(Side::Left),
+            1 => Some(Side::Right),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn snapshot(&mut self) -> Option<Snapshot> {
+        let tick = self.u32()?;
+        let ack = self.u32()?;
+        let waiting = self.u8()? != 0;
+        let ball = [self.f32()?, self.f32()?];
+        let left = self.f32()?;
+        let right = self.f32()?;
+        let score_left = self.u32()? as i32;
+        let score_right = self.u32()? as i32;
+        let bounces = self.u32()?;
+        let server = self.side()?;
+        let countdown = Some(self.f32()?).filter(|&seconds| seconds >= 0.0);
+        let winner = match self.u8()? {
+            0 => Some(Side::Left),
+            1 => Some(Side::Right),
+            2 => None,
+            _ => return None,
+        };
+        Some(Snapshot {
+            tick,
+            ack,
+            waiting,
+            ball,
+            left,
+            right,
+            score_left,
+            score_right,
+            bounces,
+            server,
+            countdown,
+            winner,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rng;
+
+    fn inputs() -> Vec<SideInput> {
+        vec![
+            SideInput {
+                movement: 1.0,
+                serve: false,
+            },
+            SideInput {
+                movement: -0.5,
+                serve: true,
+            },
+        ]
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message::Hello {
+                seed: 7,
+                config_hash: u64::MAX,
+                side: Side::Left,
+            },
+            Message::Inputs {
+                ack: 3,
+                first_frame: 10,
+                inputs: inputs(),
+                checksum: Some((60, 0x1234_5678_9abc_def0)),
+            },
+            Message::Inputs {
+                ack: 0,
+                first_frame: 0,
+                inputs: Vec::new(),
+                checksum: None,
+            },
+            Message::Join { config_hash: 42 },
+            Message::Welcome { side: Side::Right },
+            Message::Refused,
+            Message::ClientInputs {
+                first_sequence: 99,
+                inputs: inputs(),
+            },
+            Message::Snapshot(Snapshot {
+                tick: 120,
+                ack: 118,
+                waiting: false,
+                ball: [12.5, 80.25],
+                left: 30.0,
+                right: 70.0,
+                score_left: 3,
+                score_right: 11,
+                bounces: 41,
+                server: Side::Left,
+                countdown: Some(1.5),
+                winner: Some(Side::Right),
+            }),
+            Message::Snapshot(Snapshot {
+                tick: 0,
+                ack: 0,
+                waiting: true,
+                ball: [50.0, 50.0],
+                left: 50.0,
+                right: 50.0,
+                score_left: 0,
+                score_right: 0,
+                bounces: 0,
+                server: Side::Right,
+                countdown: None,
+                winner: None,
+            }),
+            Message::Watch { config_hash: 1 },
+        ]
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        for message in messages() {
+            assert_eq!(Message::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn truncated_datagrams_are_rejected() {
+        for message in messages() {
+            let datagram = message.encode();
+            for len in 0..datagram.len() {
+                assert_eq!(Message::decode(&datagram[..len]), None, "{:?} cut to {} bytes", message, len);
+            }
+        }
+    }
+
+    #[test]
+    fn other_protocols_and_versions_are_rejected() {
+        let mut datagram = Message::Refused.encode();
+        datagram[2] = VERSION + 1;
+        assert_eq!(Message::decode(&datagram), None);
+        datagram[2] = VERSION;
+        datagram[0] = b'X';
+        assert_eq!(Message::decode(&datagram), None);
+        assert_eq!(Message::decode(&[b'P', b'N', VERSION, 200]), None);
+    }
+
+    #[test]
+    fn counts_beyond_the_datagram_are_rejected() {
+        let mut datagram = Message::ClientInputs {
+            first_sequence: 0,
+            inputs: inputs(),
+        }
+        .encode();
+        // claim 65535 inputs but carry two
+        datagram[8..10].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert_eq!(Message::decode(&datagram), None);
+    }
+
+    #[test]
+    fn garbage_does_not_panic() {
+        let mut rng = Rng::new(1);
+        for _ in 0..10_000 {
+            let len = (rng.next_f32() * 64.0) as usize;
+            let mut datagram: Vec<u8> = (0..len).map(|_| (rng.next_f32() * 256.0) as u8).collect();
+            // get past the header often enough to reach the message bodies
+            if len >= 4 && rng.next_f32() < 0.75 {
+                datagram[..3].copy_from_slice(&[b'P', b'N', VERSION]);
+                datagram[3] %= 8;
+            }
+            let _ = Message::decode(&datagram);
+        }
+    }
+}
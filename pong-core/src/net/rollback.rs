@@ -0,0 +1,505 @@
+//! Peer-to-peer matches with rollback.
+//!
+//! Both peers simulate the whole match. A frame can only be simulated for certain once both
+//! sides' inputs for it are known, but waiting for the other side's input every frame would make
+//! the game as slow as the link. Instead the session guesses that the other side keeps doing
+//! what it did last, and when the real input turns out different it goes back to the last frame
+//! where every input was known and simulates forward again.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::net::protocol::{sanitize, MAX_INPUTS_PER_DATAGRAM};
+use crate::net::{Message, SideInput, Transport};
+use crate::{Arena, Event, Input, Side};
+
+// both peers compare checksums every this many confirmed frames
+const CHECKSUM_INTERVAL: u32 = 60;
+// how far past our own frame the other side's inputs are taken. No honest peer gets anywhere
+// near, and inputs it does send beyond this are sent again until we have them.
+const INPUT_WINDOW: u32 = 512;
+
+#[derive(Clone, Debug)]
+pub struct RollbackSettings {
+    /// Frames between reading a local input and simulating it. A delay that covers the link's
+    /// latency avoids most rollbacks, at the cost of the paddle answering later.
+    pub input_delay: u32,
+    /// How many frames the simulation may get ahead of the last frame with both inputs known
+    /// before it waits for the other side.
+    pub max_prediction: u32,
+}
+
+impl Default for RollbackSettings {
+    fn default() -> RollbackSettings {
+        RollbackSettings {
+            input_delay: 2,
+            max_prediction: 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    /// The other side plays with a different configuration, so the matches would drift apart.
+    ConfigMismatch,
+    /// The other side wants to play the same paddle as we do.
+    SameSide,
+    /// The two sides' arenas differed after this many frames.
+    Desync(u32),
+    /// The server turned us away, because the match is full or uses a different configuration.
+    Refused,
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Io(e) => write!(f, "{}", e),
+            NetError::ConfigMismatch => write!(f, "the other side uses a different configuration"),
+            NetError::SameSide => write!(f, "the other side plays the same paddle"),
+            NetError::Desync(frame) => write!(f, "the match went out of sync at frame {}", frame),
+            NetError::Refused => write!(f, "the server refused to let us join or watch"),
+        }
+    }
+}
+
+impl Error for NetError {}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> NetError {
+        NetError::Io(e)
+    }
+}
+
+/// One side of a peer-to-peer match.
+pub struct RollbackSession<T> {
+    transport: T,
+    local: Side,
+    settings: RollbackSettings,
+    seed: u64,
+    config_hash: u64,
+    /// The other side's hello has arrived, so the match can start.
+    connected: bool,
+    /// The other side has started sending inputs, so it has our hello.
+    peer_started: bool,
+    /// The arena after `frame` frames, partly predicted.
+    arena: Arena,
+    /// The arena after `confirmed_frame` frames, all of them with known inputs.
+    confirmed: Arena,
+    frame: u32,
+    confirmed_frame: u32,
+    local_inputs: Vec<SideInput>,
+    remote_inputs: Vec<Option<SideInput>>,
+    /// The other side's input each simulated frame was run with.
+    used_remote: Vec<SideInput>,
+    /// Frames from 0 of the other side's input that have arrived without a gap.
+    remote_received: u32,
+    /// Frames from 0 of our input the other side has.
+    remote_ack: u32,
+    checksums: BTreeMap<u32, u64>,
+    remote_checksum: Option<(u32, u64)>,
+    needs_rollback: bool,
+    rollbacks: u32,
+}
+
+impl<T: Transport> RollbackSession<T> {
+    /// Starts a session controlling `local`. `arena` has to be set up the same way on both
+    /// sides; the serves are drawn from the left side's `seed`. `config_hash` identifies the
+    /// configuration, see `replay::config_hash`.
+    pub fn new(
+        transport: T,
+        local: Side,
+        arena: Arena,
+        seed: u64,
+        config_hash: u64,
+        settings: RollbackSettings,
+    ) -> RollbackSession<T> {
+        let arena = arena.with_seed(seed);
+        RollbackSession {
+            transport,
+            local,
+            seed,
+            config_hash,
+            connected: false,
+            peer_started: false,
+            confirmed: arena.clone(),
+            arena,
+            frame: 0,
+            confirmed_frame: 0,
+            // the first frames run before any local input can reach them
+            local_inputs: vec![SideInput::default(); settings.input_delay as usize],
+            remote_inputs: Vec::new(),
+            used_remote: Vec::new(),
+            remote_received: 0,
+            remote_ack: 0,
+            checksums: BTreeMap::new(),
+            remote_checksum: None,
+            needs_rollback: false,
+            rollbacks: 0,
+            settings,
+        }
+    }
+
+    /// The match as this side currently sees it.
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    /// The match up to the last frame with both sides' inputs known. Unlike `arena`, nothing in
+    /// it can be undone by an input arriving late, so both sides agree on it; it decides when
+    /// the match is over.
+    pub fn confirmed_arena(&self) -> &Arena {
+        &self.confirmed
+    }
+
+    pub fn local_side(&self) -> Side {
+        self.local
+    }
+
+    /// Frames simulated so far.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// How many of the simulated frames used a guess for the other side's input.
+    pub fn prediction_depth(&self) -> u32 {
+        self.frame - self.confirmed_frame
+    }
+
+    /// How many times a wrong guess made the session simulate frames again.
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Takes this side's input for the next frame and simulates it. Returns the frame's events,
+    /// or `None` if the session is waiting: for the other side to connect, or for its inputs
+    /// once the simulation is `max_prediction` frames ahead of them.
+    pub fn advance(&mut self, local: SideInput, dt: f32) -> Result<Option<Vec<Event>>, NetError> {
+        self.receive()?;
+        self.confirm(dt)?;
+        if self.needs_rollback {
+            self.resimulate(dt);
+        }
+
+        if !self.connected || self.prediction_depth() >= self.settings.max_prediction {
+            self.send()?;
+            return Ok(None);
+        }
+
+        self.local_inputs.push(local);
+        self.send()?;
+
+        let frame = self.frame as usize;
+        let remote = self.remote_input(self.frame);
+        self.used_remote.push(remote);
+        let events = self.arena.step(self.combine(self.local_inputs[frame], remote), dt);
+        self.frame += 1;
+        Ok(Some(events))
+    }
+
+    fn receive(&mut self) -> Result<(), NetError> {
+        while let Some(datagram) = self.transport.recv()? {
+            match Message::decode(&datagram) {
+                Some(Message::Hello { seed, config_hash, side }) => {
+                    if config_hash != self.config_hash {
+                        return Err(NetError::ConfigMismatch);
+                    }
+                    if side == self.local {
+                        // the other side may not have our hello yet, and needs it to stop too
+                        self.transport.send(&self.hello().encode())?;
+                        return Err(NetError::SameSide);
+                    }
+                    if !self.connected {
+                        self.connected = true;
+                        if self.local == Side::Right {
+                            self.arena = self.arena.clone().with_seed(seed);
+                            self.confirmed = self.arena.clone();
+                        }
+                    }
+                }
+                Some(Message::Inputs { ack, first_frame, inputs, checksum }) => {
+                    if !self.connected {
+                        continue;
+                    }
+                    self.peer_started = true;
+                    self.remote_ack = self.remote_ack.max(ack);
+                    // the frame numbers come off the network, so they mustn't decide how much
+                    // is allocated
+                    let window = self.frame.saturating_add(INPUT_WINDOW);
+                    for (i, input) in inputs.into_iter().enumerate() {
+                        match first_frame.checked_add(i as u32) {
+                            Some(frame) if frame < window => self.receive_input(frame as usize, sanitize(input)),
+                            _ => break,
+                        }
+                    }
+                    if let Some(checksum) = checksum {
+                        self.remote_checksum = Some(checksum);
+                        self.check_sync()?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn receive_input(&mut self, frame: usize, input: SideInput) {
+        if self.remote_inputs.len() <= frame {
+            self.remote_inputs.resize(frame + 1, None);
+        }
+        if self.remote_inputs[frame].is_some() {
+            return;
+        }
+        self.remote_inputs[frame] = Some(input);
+        // a frame that already ran with a different guess has to run again
+        if self.used_remote.get(frame).is_some_and(|&used| used != input) {
+            self.needs_rollback = true;
+        }
+        while self.remote_inputs.get(self.remote_received as usize).is_some_and(Option::is_some) {
+            self.remote_received += 1;
+        }
+    }
+
+    // moves the confirmed arena up to the last simulated frame with both inputs known
+    fn confirm(&mut self, dt: f32) -> Result<(), NetError> {
+        while self.confirmed_frame < self.frame.min(self.remote_received) {
+            let frame = self.confirmed_frame as usize;
+            let remote = self.remote_inputs[frame].unwrap_or_default();
+            self.confirmed.step(self.combine(self.local_inputs[frame], remote), dt);
+            self.confirmed_frame += 1;
+
+            if self.confirmed_frame.is_multiple_of(CHECKSUM_INTERVAL) {
+                self.checksums.insert(self.confirmed_frame, self.confirmed.checksum());
+                self.check_sync()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn resimulate(&mut self, dt: f32) {
+        self.arena = self.confirmed.clone();
+        for frame in self.confirmed_frame..self.frame {
+            let remote = self.remote_input(frame);
+            self.used_remote[frame as usize] = remote;
+            self.arena.step(self.combine(self.local_inputs[frame as usize], remote), dt);
+        }
+        self.needs_rollback = false;
+        self.rollbacks += 1;
+    }
+
+    fn check_sync(&self) -> Result<(), NetError> {
+        if let Some((frame, remote)) = self.remote_checksum {
+            if self.checksums.get(&frame).is_some_and(|&local| local != remote) {
+                return Err(NetError::Desync(frame));
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self) -> io::Result<()> {
+        if !self.peer_started {
+            self.transport.send(&self.hello().encode())?;
+        }
+        if !self.connected {
+            return Ok(());
+        }
+
+        // everything the other side hasn't confirmed yet, oldest first
+        let first = (self.remote_ack as usize).min(self.local_inputs.len());
+        let last = (first + MAX_INPUTS_PER_DATAGRAM).min(self.local_inputs.len());
+        let inputs = Message::Inputs {
+            ack: self.remote_received,
+            first_frame: first as u32,
+            inputs: self.local_inputs[first..last].to_vec(),
+            checksum: self.checksums.iter().next_back().map(|(&frame, &checksum)| (frame, checksum)),
+        };
+        self.transport.send(&inputs.encode())
+    }
+
+    fn hello(&self) -> Message {
+        Message::Hello {
+            seed: self.seed,
+            config_hash: self.config_hash,
+            side: self.local,
+        }
+    }
+
+    // the other side's input for `frame`, or a guess if it hasn't arrived
+    fn remote_input(&self, frame: u32) -> SideInput {
+        if let Some(Some(input)) = self.remote_inputs.get(frame as usize) {
+            return *input;
+        }
+        match self.remote_received {
+            0 => SideInput::default(),
+            received => self.remote_inputs[received as usize - 1].unwrap_or_default(),
+        }
+    }
+
+    fn combine(&self, local: SideInput, remote: SideInput) -> Input {
+        let (left, right) = match self.local {
+            Side::Left => (local, remote),
+            Side::Right => (remote, local),
+        };
+        Input {
+            left: left.movement,
+            right: right.movement,
+            serve_left: left.serve,
+            serve_right: right.serve,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::net::{LinkConditions, SimulatedTransport, UdpTransport};
+
+    const DT: f32 = 1.0 / 120.0;
+
+    fn session<T: Transport>(transport: T, side: Side) -> RollbackSession<T> {
+        RollbackSession::new(transport, side, Arena::default(), 5, 99, RollbackSettings::default())
+    }
+
+    // a paddle that changes direction every few dozen frames, so guesses that it keeps doing
+    // what it did last keep going wrong
+    fn input(frame: u32, period: u32) -> SideInput {
+        SideInput {
+            movement: if (frame / period).is_multiple_of(2) { 1.0 } else { -1.0 },
+            serve: true,
+        }
+    }
+
+    #[test]
+    fn peers_agree_after_mispredictions() {
+        let (left, right) = UdpTransport::pair().unwrap();
+        // six frames of latency each way, longer than the input delay, so both sides predict
+        let mut left = SimulatedTransport::new(left, 1);
+        left.set_conditions(LinkConditions {
+            latency: 0.05,
+            ..LinkConditions::default()
+        });
+        let mut left = session(left, Side::Left);
+        let mut right = session(right, Side::Right);
+
+        // the confirmed arena's checksum at each confirmed frame, per side
+        let mut confirmed = [HashMap::new(), HashMap::new()];
+        for _ in 0..1200 {
+            left.transport_mut().advance(DT).unwrap();
+            left.advance(input(left.frame(), 37), DT).unwrap();
+            right.advance(input(right.frame(), 23), DT).unwrap();
+            confirmed[0].insert(left.confirmed_frame, left.confirmed_arena().checksum());
+            confirmed[1].insert(right.confirmed_frame, right.confirmed_arena().checksum());
+        }
+
+        assert!(left.rollbacks() > 0 && right.rollbacks() > 0);
+        let common: Vec<_> = confirmed[0].keys().filter(|frame| confirmed[1].contains_key(frame)).collect();
+        assert!(common.len() > 500, "only {} frames confirmed on both sides", common.len());
+        for frame in common {
+            assert_eq!(confirmed[0][frame], confirmed[1][frame], "arenas differ after frame {}", frame);
+        }
+    }
+
+    // sends `messages` from the other side and advances until the inputs among them are in
+    fn receive_from_peer(
+        session: &mut RollbackSession<UdpTransport>,
+        remote: &mut UdpTransport,
+        messages: &[Message],
+    ) {
+        for message in messages {
+            remote.send(&message.encode()).unwrap();
+        }
+        for _ in 0..100 {
+            session.advance(SideInput::default(), DT).unwrap();
+            if session.peer_started {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("the peer's inputs never arrived");
+    }
+
+    #[test]
+    fn inputs_far_ahead_are_dropped() {
+        let (local, mut remote) = UdpTransport::pair().unwrap();
+        let mut session = session(local, Side::Left);
+        let hello = Message::Hello {
+            seed: 5,
+            config_hash: 99,
+            side: Side::Right,
+        };
+        let inputs = Message::Inputs {
+            ack: 0,
+            first_frame: u32::MAX - 1,
+            inputs: vec![SideInput::default(); 4],
+            checksum: None,
+        };
+        receive_from_peer(&mut session, &mut remote, &[hello, inputs]);
+        assert!(session.remote_inputs.is_empty());
+    }
+
+    #[test]
+    fn wild_movements_are_clamped() {
+        let (local, mut remote) = UdpTransport::pair().unwrap();
+        let mut session = session(local, Side::Left);
+        let hello = Message::Hello {
+            seed: 5,
+            config_hash: 99,
+            side: Side::Right,
+        };
+        let movements = [1e9, f32::NAN, f32::NEG_INFINITY, -0.5];
+        let inputs = Message::Inputs {
+            ack: 0,
+            first_frame: 0,
+            inputs: movements.iter().map(|&movement| SideInput { movement, serve: false }).collect(),
+            checksum: None,
+        };
+        receive_from_peer(&mut session, &mut remote, &[hello, inputs]);
+
+        let received: Vec<f32> = session.remote_inputs.iter().map(|input| input.unwrap().movement).collect();
+        assert_eq!(received, vec![1.0, 0.0, 0.0, -0.5]);
+        for _ in 0..10 {
+            session.advance(SideInput::default(), DT).unwrap();
+        }
+        let right = &session.arena().right;
+        assert!(right.position[1].is_finite());
+        assert!(right.position[1] >= 0.0 && right.position[1] <= session.arena().height);
+    }
+
+    #[test]
+    fn peers_on_the_same_side_are_refused() {
+        let (left, other_left) = UdpTransport::pair().unwrap();
+        let mut sessions = [session(left, Side::Left), session(other_left, Side::Left)];
+        let mut errors = [None, None];
+        for _ in 0..100 {
+            for (session, error) in sessions.iter_mut().zip(errors.iter_mut()) {
+                if error.is_none() {
+                    *error = session.advance(SideInput::default(), DT).err();
+                }
+            }
+            if errors.iter().all(Option::is_some) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        for error in &errors {
+            assert!(matches!(error, Some(NetError::SameSide)), "{:?}", error);
+        }
+        assert!(sessions.iter().all(|session| !session.is_connected()));
+    }
+}
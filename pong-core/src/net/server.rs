@@ -0,0 +1,588 @@
+//! Matches run by a server.
+//!
+//! Only the server simulates the arena. Clients send it nothing but their paddle inputs and get
+//! snapshots of the match back, so neither of them can move the ball, the other paddle or the
+//! score. The server uses each client's inputs in the order they were numbered, one per tick.
+//!
+//! Up to `max_spectators` spectators can watch as well. They get the same snapshots, but only
+//! after `spectator_delay`, so that a player glancing at a spectator's screen learns nothing new.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use log::warn;
+
+use crate::net::protocol::sanitize;
+use crate::net::{Message, SideInput, Snapshot};
+use crate::{Arena, Event, Input, Side};
+
+// large enough for any message in `protocol`
+const MAX_DATAGRAM: usize = 1500;
+
+#[derive(Clone, Debug)]
+pub struct ServerSettings {
+    /// Ticks between snapshots sent to each client.
+    pub snapshot_interval: u32,
+    /// Most inputs queued for one client. A client that gets further ahead of the server than
+    /// this has its oldest inputs dropped, so its paddle doesn't fall behind for good.
+    pub max_queued_inputs: usize,
+    /// Seconds without a datagram after which a client loses its paddle, or a spectator stops
+    /// getting snapshots. The match waits until someone joins in the player's place.
+    pub timeout: f32,
+    /// Seconds spectators see the match behind the players.
+    pub spectator_delay: f32,
+    /// Most spectators watching at once. Each of them gets a snapshot every tick, so anyone
+    /// past this is refused rather than left to eat up the server's bandwidth.
+    pub max_spectators: usize,
+}
+
+impl Default for ServerSettings {
+    fn default() -> ServerSettings {
+        ServerSettings {
+            snapshot_interval: 1,
+            max_queued_inputs: 8,
+            timeout: 5.0,
+            spectator_delay: 0.0,
+            max_spectators: 16,
+        }
+    }
+}
+
+// a client that holds one of the paddles
+struct Player {
+    addr: SocketAddr,
+    queued: VecDeque<SideInput>,
+    /// Inputs from 0 that have arrived without a gap.
+    received: u32,
+    /// Inputs from 0 that have been used or dropped.
+    used: u32,
+    last: SideInput,
+    silent: f32,
+}
+
+impl Player {
+    fn new(addr: SocketAddr) -> Player {
+        Player {
+            addr,
+            queued: VecDeque::new(),
+            received: 0,
+            used: 0,
+            last: SideInput::default(),
+            silent: 0.0,
+        }
+    }
+
+    // the input for the next tick; a client whose input is late keeps moving the same way
+    fn next_input(&mut self) -> SideInput {
+        match self.queued.pop_front() {
+            Some(input) => {
+                self.used += 1;
+                self.last = input;
+                input
+            }
+            None => SideInput {
+                movement: self.last.movement,
+                serve: false,
+            },
+        }
+    }
+}
+
+// a client that only watches
+struct Spectator {
+    addr: SocketAddr,
+    silent: f32,
+}
+
+/// A match served over UDP to two clients, see `ClientSession`. Clients get the left paddle,
+/// then the right one, in the order they join.
+pub struct MatchServer {
+    socket: UdpSocket,
+    arena: Arena,
+    config_hash: u64,
+    settings: ServerSettings,
+    players: [Option<Player>; 2],
+    spectators: Vec<Spectator>,
+    /// Snapshots from the last `spectator_delay` seconds, oldest first.
+    delayed: VecDeque<Snapshot>,
+    tick: u32,
+    updates: u32,
+    bounces: u32,
+}
+
+impl MatchServer {
+    /// Listens on `addr` for clients of a match in `arena`. Clients have to have the same
+    /// `config_hash`, see `replay::config_hash`, or they are refused.
+    pub fn bind<A: ToSocketAddrs>(
+        addr: A,
+        arena: Arena,
+        config_hash: u64,
+        settings: ServerSettings,
+    ) -> io::Result<MatchServer> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(MatchServer {
+            socket,
+            arena,
+            config_hash,
+            settings,
+            players: [None, None],
+            spectators: Vec::new(),
+            delayed: VecDeque::new(),
+            tick: 0,
+            updates: 0,
+            bounces: 0,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    /// Ticks the match has been played for.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// The address of the client playing `side`, if there is one.
+    pub fn player(&self, side: Side) -> Option<SocketAddr> {
+        self.players[side as usize].as_ref().map(|player| player.addr)
+    }
+
+    /// The addresses of everyone watching.
+    pub fn spectators(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.spectators.iter().map(|spectator| spectator.addr)
+    }
+
+    /// Both paddles have a client, so the match is being played.
+    pub fn is_running(&self) -> bool {
+        self.players.iter().all(Option::is_some)
+    }
+
+    /// Handles whatever the clients sent, plays one tick of `dt` seconds if both paddles are
+    /// taken and sends out snapshots. Returns the tick's events.
+    pub fn update(&mut self, dt: f32) -> io::Result<Vec<Event>> {
+        self.receive()?;
+        self.drop_silent_players(dt);
+
+        let mut events = Vec::new();
+        if self.is_running() && !self.arena.state.is_over() {
+            let mut inputs = [SideInput::default(); 2];
+            for (input, player) in inputs.iter_mut().zip(self.players.iter_mut()) {
+                if let Some(player) = player {
+                    *input = player.next_input();
+                }
+            }
+            events = self.arena.step(combine(inputs), dt);
+            self.bounces += events.iter().filter(|&&event| event == Event::Bounce).count() as u32;
+            self.tick += 1;
+        }
+
+        // spectators see the snapshot from `spectator_delay` ago, or the oldest one there is
+        let delay = (self.settings.spectator_delay / dt).round() as usize;
+        self.delayed.push_back(self.snapshot(0));
+        while self.delayed.len() > delay + 1 {
+            self.delayed.pop_front();
+        }
+
+        self.updates += 1;
+        if self.updates.is_multiple_of(self.settings.snapshot_interval.max(1)) {
+            self.send_snapshots();
+        }
+        Ok(events)
+    }
+
+    fn receive(&mut self) -> io::Result<()> {
+        let mut buffer = [0; MAX_DATAGRAM];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => continue,
+                Err(e) => return Err(e),
+            };
+            match Message::decode(&buffer[..len]) {
+                Some(Message::Join { config_hash }) => self.join(from, config_hash),
+                Some(Message::ClientInputs { first_sequence, inputs }) => {
+                    self.receive_inputs(from, first_sequence, inputs)
+                }
+                Some(Message::Watch { config_hash }) => self.watch(from, config_hash),
+                _ => {}
+            }
+        }
+    }
+
+    fn join(&mut self, from: SocketAddr, config_hash: u64) {
+        let side = match self.side_of(from) {
+            Some(side) => Some(side),
+            None if config_hash != self.config_hash => None,
+            None => {
+                let free = self.players.iter().position(Option::is_none);
+                free.map(|index| {
+                    self.players[index] = Some(Player::new(from));
+                    if index == 0 { Side::Left } else { Side::Right }
+                })
+            }
+        };
+        let answer = match side {
+            Some(side) => Message::Welcome { side },
+            None => Message::Refused,
+        };
+        self.send_to(&answer, from);
+    }
+
+    fn watch(&mut self, from: SocketAddr, config_hash: u64) {
+        if config_hash != self.config_hash {
+            self.send_to(&Message::Refused, from);
+            return;
+        }
+        let full = self.spectators.len() >= self.settings.max_spectators;
+        match self.spectators.iter_mut().find(|spectator| spectator.addr == from) {
+            Some(spectator) => spectator.silent = 0.0,
+            None if full => self.send_to(&Message::Refused, from),
+            None => self.spectators.push(Spectator { addr: from, silent: 0.0 }),
+        }
+    }
+
+    fn receive_inputs(&mut self, from: SocketAddr, first_sequence: u32, inputs: Vec<SideInput>) {
+        let side = match self.side_of(from) {
+            Some(side) => side,
+            None => return,
+        };
+        let max_queued = self.settings.max_queued_inputs.max(1);
+        let player = match self.players[side as usize].as_mut() {
+            Some(player) => player,
+            None => return,
+        };
+        player.silent = 0.0;
+        for (i, input) in inputs.into_iter().enumerate() {
+            // the numbers come off the network and may be anything, up to u32::MAX
+            if first_sequence.checked_add(i as u32) != Some(player.received) {
+                continue;
+            }
+            player.queued.push_back(sanitize(input));
+            player.received += 1;
+        }
+        while player.queued.len() > max_queued {
+            player.queued.pop_front();
+            player.used += 1;
+        }
+    }
+
+    fn drop_silent_players(&mut self, dt: f32) {
+        let timeout = self.settings.timeout;
+        for slot in self.players.iter_mut() {
+            let timed_out = slot.as_mut().is_some_and(|player| {
+                player.silent += dt;
+                player.silent > timeout
+            });
+            if timed_out {
+                *slot = None;
+            }
+        }
+        for spectator in self.spectators.iter_mut() {
+            spectator.silent += dt;
+        }
+        self.spectators.retain(|spectator| spectator.silent <= timeout);
+    }
+
+    fn send_snapshots(&self) {
+        for player in self.players.iter().flatten() {
+            let snapshot = self.snapshot(player.used);
+            self.send_to(&Message::Snapshot(snapshot), player.addr);
+        }
+        if let Some(snapshot) = self.delayed.front() {
+            let message = Message::Snapshot(snapshot.clone());
+            for spectator in &self.spectators {
+                self.send_to(&message, spectator.addr);
+            }
+        }
+    }
+
+    fn snapshot(&self, ack: u32) -> Snapshot {
+        Snapshot {
+            tick: self.tick,
+            ack,
+            waiting: !self.is_running(),
+            ball: self.arena.ball.position,
+            left: self.arena.left.position[1],
+            right: self.arena.right.position[1],
+            score_left: self.arena.scores.score_left,
+            score_right: self.arena.scores.score_right,
+            bounces: self.bounces,
+            server: self.arena.serve.server,
+            countdown: self.arena.serve.countdown,
+            winner: self.arena.state.winner,
+        }
+    }
+
+    fn side_of(&self, addr: SocketAddr) -> Option<Side> {
+        match &self.players {
+            [Some(left), _] if left.addr == addr => Some(Side::Left),
+            [_, Some(right)] if right.addr == addr => Some(Side::Right),
+            _ => None,
+        }
+    }
+
+    // one client that can't be reached mustn't stop the match for everyone else, so failed
+    // sends are only logged; a client that never hears from us stops talking and times out
+    fn send_to(&self, message: &Message, addr: SocketAddr) {
+        match self.socket.send_to(&message.encode(), addr) {
+            Ok(_) => {}
+            // the client has gone away
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(e) => warn!("could not send to {}: {}", addr, e),
+        }
+    }
+}
+
+fn combine([left, right]: [SideInput; 2]) -> Input {
+    Input {
+        left: left.movement,
+        right: right.movement,
+        serve_left: left.serve,
+        serve_right: right.serve,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const DT: f32 = 0.1;
+    const CONFIG_HASH: u64 = 99;
+
+    fn server(settings: ServerSettings) -> MatchServer {
+        MatchServer::bind("127.0.0.1:0", Arena::default(), CONFIG_HASH, settings).unwrap()
+    }
+
+    fn client(server: &MatchServer) -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.local_addr().unwrap()).unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        socket
+    }
+
+    fn send(socket: &UdpSocket, message: Message) {
+        socket.send(&message.encode()).unwrap();
+    }
+
+    // the next message that isn't a snapshot, skipping those
+    fn answer(socket: &UdpSocket) -> Message {
+        let mut buffer = [0; MAX_DATAGRAM];
+        loop {
+            let len = socket.recv(&mut buffer).unwrap();
+            match Message::decode(&buffer[..len]).unwrap() {
+                Message::Snapshot(_) => continue,
+                message => return message,
+            }
+        }
+    }
+
+    // the newest snapshot that has arrived
+    fn last_snapshot(socket: &UdpSocket) -> Snapshot {
+        let mut buffer = [0; MAX_DATAGRAM];
+        let mut last = None;
+        socket.set_nonblocking(true).unwrap();
+        while let Ok(len) = socket.recv(&mut buffer) {
+            if let Some(Message::Snapshot(snapshot)) = Message::decode(&buffer[..len]) {
+                last = Some(snapshot);
+            }
+        }
+        socket.set_nonblocking(false).unwrap();
+        last.expect("no snapshot arrived")
+    }
+
+    fn join(server: &mut MatchServer, socket: &UdpSocket) -> Message {
+        send(socket, Message::Join { config_hash: CONFIG_HASH });
+        server.update(DT).unwrap();
+        answer(socket)
+    }
+
+    fn inputs(first_sequence: u32, movements: &[f32]) -> Message {
+        Message::ClientInputs {
+            first_sequence,
+            inputs: movements
+                .iter()
+                .map(|&movement| SideInput { movement, serve: false })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn clients_get_the_paddles_in_order() {
+        let mut server = server(ServerSettings::default());
+        let (left, right, third) = (client(&server), client(&server), client(&server));
+        assert_eq!(join(&mut server, &left), Message::Welcome { side: Side::Left });
+        assert_eq!(join(&mut server, &right), Message::Welcome { side: Side::Right });
+        assert_eq!(join(&mut server, &third), Message::Refused);
+        // joining again is answered the same way
+        assert_eq!(join(&mut server, &left), Message::Welcome { side: Side::Left });
+        assert!(server.is_running());
+
+        let stranger = client(&server);
+        send(&stranger, Message::Join { config_hash: CONFIG_HASH + 1 });
+        server.update(DT).unwrap();
+        assert_eq!(answer(&stranger), Message::Refused);
+    }
+
+    #[test]
+    fn inputs_are_queued_in_sequence() {
+        let mut server = server(ServerSettings::default());
+        let left = client(&server);
+        join(&mut server, &left);
+
+        // inputs after a gap are dropped until it is filled, and repeats are skipped
+        send(&left, inputs(2, &[0.3]));
+        send(&left, inputs(0, &[0.1, 0.2]));
+        send(&left, inputs(0, &[0.1, 0.2, 0.3, 5.0]));
+        server.update(DT).unwrap();
+
+        let player = server.players[0].as_ref().unwrap();
+        assert_eq!(player.received, 4);
+        let queued: Vec<f32> = player.queued.iter().map(|input| input.movement).collect();
+        // the out of range movement is clamped
+        assert_eq!(queued, vec![0.1, 0.2, 0.3, 1.0]);
+    }
+
+    #[test]
+    fn queue_drops_the_oldest_inputs_beyond_its_limit() {
+        let mut server = server(ServerSettings {
+            max_queued_inputs: 3,
+            ..ServerSettings::default()
+        });
+        let left = client(&server);
+        join(&mut server, &left);
+
+        send(&left, inputs(0, &[0.1, 0.2, 0.3, 0.4, 0.5]));
+        server.update(DT).unwrap();
+
+        let player = server.players[0].as_ref().unwrap();
+        let queued: Vec<f32> = player.queued.iter().map(|input| input.movement).collect();
+        assert_eq!(queued, vec![0.3, 0.4, 0.5]);
+        assert_eq!((player.received, player.used), (5, 2));
+    }
+
+    #[test]
+    fn sequence_numbers_near_the_limit_are_ignored() {
+        let mut server = server(ServerSettings::default());
+        let left = client(&server);
+        join(&mut server, &left);
+
+        send(&left, inputs(u32::MAX - 1, &[0.1, 0.2, 0.3, 0.4]));
+        server.update(DT).unwrap();
+
+        let player = server.players[0].as_ref().unwrap();
+        assert_eq!(player.received, 0);
+        assert!(player.queued.is_empty());
+    }
+
+    #[test]
+    fn silent_player_loses_the_paddle_to_a_newcomer() {
+        let mut server = server(ServerSettings {
+            timeout: 1.0,
+            ..ServerSettings::default()
+        });
+        let (left, right, newcomer) = (client(&server), client(&server), client(&server));
+        join(&mut server, &left);
+        join(&mut server, &right);
+
+        // only the right player keeps talking
+        for sequence in 0..12 {
+            send(&right, inputs(sequence, &[0.0]));
+            server.update(DT).unwrap();
+        }
+        assert_eq!(server.player(Side::Left), None);
+        assert!(server.player(Side::Right).is_some());
+        assert!(!server.is_running());
+        let tick = server.tick();
+        server.update(DT).unwrap();
+        assert_eq!(server.tick(), tick, "the match waits for a new player");
+
+        assert_eq!(join(&mut server, &newcomer), Message::Welcome { side: Side::Left });
+        assert!(server.is_running());
+    }
+
+    #[test]
+    fn spectators_see_the_match_late() {
+        let mut server = server(ServerSettings {
+            spectator_delay: 0.5,
+            ..ServerSettings::default()
+        });
+        let (left, right, spectator) = (client(&server), client(&server), client(&server));
+        join(&mut server, &left);
+        join(&mut server, &right);
+        send(&spectator, Message::Watch { config_hash: CONFIG_HASH });
+
+        for _ in 0..20 {
+            server.update(DT).unwrap();
+        }
+        // 0.5 seconds of 0.1 second ticks
+        assert_eq!(server.delayed.len(), 6);
+        assert_eq!(last_snapshot(&spectator).tick, server.tick() - 5);
+        assert_eq!(last_snapshot(&left).tick, server.tick());
+    }
+
+    #[test]
+    fn spectators_without_delay_see_the_match_live() {
+        let mut server = server(ServerSettings::default());
+        let (left, right, spectator) = (client(&server), client(&server), client(&server));
+        join(&mut server, &left);
+        join(&mut server, &right);
+        send(&spectator, Message::Watch { config_hash: CONFIG_HASH });
+
+        for _ in 0..5 {
+            server.update(DT).unwrap();
+        }
+        assert_eq!(server.spectators().count(), 1);
+        let snapshot = last_snapshot(&spectator);
+        assert_eq!((snapshot.tick, snapshot.ack), (server.tick(), 0));
+    }
+
+    #[test]
+    fn spectators_beyond_the_limit_are_refused() {
+        let mut server = server(ServerSettings {
+            max_spectators: 2,
+            ..ServerSettings::default()
+        });
+        let watchers = [client(&server), client(&server), client(&server)];
+        for watcher in &watchers {
+            send(watcher, Message::Watch { config_hash: CONFIG_HASH });
+            server.update(DT).unwrap();
+        }
+        assert_eq!(server.spectators().count(), 2);
+        assert_eq!(answer(&watchers[2]), Message::Refused);
+
+        // those already watching may keep asking
+        send(&watchers[0], Message::Watch { config_hash: CONFIG_HASH });
+        server.update(DT).unwrap();
+        assert_eq!(server.spectators().count(), 2);
+    }
+
+    #[test]
+    fn unreachable_clients_do_not_stop_the_match() {
+        let mut server = server(ServerSettings::default());
+        let (left, spectator) = (client(&server), client(&server));
+        join(&mut server, &left);
+        // nothing can be sent to the broadcast address from a socket that isn't allowed to
+        let unreachable: SocketAddr = "255.255.255.255:7100".parse().unwrap();
+        server.players[1] = Some(Player::new(unreachable));
+        server.spectators.push(Spectator { addr: unreachable, silent: 0.0 });
+        send(&spectator, Message::Watch { config_hash: CONFIG_HASH });
+
+        for _ in 0..5 {
+            send(&left, Message::Join { config_hash: CONFIG_HASH });
+            server.update(DT).unwrap();
+        }
+        assert_eq!(server.tick(), 5);
+        assert_eq!(last_snapshot(&left).tick, 5);
+        assert_eq!(last_snapshot(&spectator).tick, 5);
+    }
+}
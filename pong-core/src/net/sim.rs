@@ -0,0 +1,381 @@
+//! Bad network links on demand.
+//!
+//! `SimulatedTransport` wraps another transport and holds back, drops, duplicates and reorders
+//! the datagrams passing through it in both directions, so a match can be played over a link as
+//! bad as needed on one machine. It keeps its own clock, moved on by `advance`, so the delays
+//! are in simulated time and a test can run much faster than real time.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::net::Transport;
+use crate::Rng;
+
+/// How bad a link is, in each direction. Chances run from 0.0 (never) to 1.0 (always).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct LinkConditions {
+    /// Seconds every datagram takes to arrive.
+    pub latency: f32,
+    /// Up to this many seconds more or less than `latency`, drawn for each datagram.
+    pub jitter: f32,
+    /// Chance that a datagram is lost.
+    pub loss: f32,
+    /// Chance that a datagram arrives twice, the copy with a delay of its own.
+    pub duplicate: f32,
+    /// Chance that a datagram is held back by another `reorder_delay` seconds, so that later
+    /// ones overtake it.
+    pub reorder: f32,
+    pub reorder_delay: f32,
+}
+
+/// One stretch of a scenario.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Phase {
+    pub name: String,
+    pub seconds: f32,
+    pub conditions: LinkConditions,
+}
+
+/// Link conditions that change over time, loaded from a RON file. The phases run in order.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub phases: Vec<Phase>,
+}
+
+impl Scenario {
+    pub fn seconds(&self) -> f32 {
+        self.phases.iter().map(|phase| phase.seconds).sum()
+    }
+
+    /// The phase running `seconds` into the scenario, or `None` once it is over.
+    pub fn phase_at(&self, seconds: f32) -> Option<(usize, &Phase)> {
+        let mut end = 0.0;
+        for (index, phase) in self.phases.iter().enumerate() {
+            end += phase.seconds;
+            if seconds < end {
+                return Some((index, phase));
+            }
+        }
+        None
+    }
+}
+
+// a datagram on its way
+struct InFlight {
+    arrives: f64,
+    datagram: Vec<u8>,
+}
+
+/// A transport that passes datagrams through `inner` under `LinkConditions`. Wrapping one end of
+/// a link is enough: what it sends is delayed before it goes out, and what it receives before it
+/// is handed over.
+pub struct SimulatedTransport<T> {
+    inner: T,
+    conditions: LinkConditions,
+    rng: Rng,
+    // seconds since the link was made; in f64, as f32 seconds can't tell milliseconds apart
+    // after a few hours
+    clock: f64,
+    outgoing: Vec<InFlight>,
+    incoming: Vec<InFlight>,
+    sent: u64,
+    dropped: u64,
+}
+
+impl<T: Transport> SimulatedTransport<T> {
+    /// Wraps `inner` with a perfect link. The losses and delays are drawn from `seed`.
+    pub fn new(inner: T, seed: u64) -> SimulatedTransport<T> {
+        SimulatedTransport {
+            inner,
+            conditions: LinkConditions::default(),
+            rng: Rng::new(seed),
+            clock: 0.0,
+            outgoing: Vec::new(),
+            incoming: Vec::new(),
+            sent: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn conditions(&self) -> &LinkConditions {
+        &self.conditions
+    }
+
+    /// Changes the link for datagrams from now on. Those already on their way keep their delay.
+    pub fn set_conditions(&mut self, conditions: LinkConditions) {
+        self.conditions = conditions;
+    }
+
+    /// Datagrams that went into the link in either direction, and how many of them were lost.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Moves the link's clock on by `seconds` and sends whatever is due.
+    pub fn advance(&mut self, seconds: f32) -> io::Result<()> {
+        self.clock += f64::from(seconds);
+        for datagram in take_due(&mut self.outgoing, self.clock) {
+            self.inner.send(&datagram)?;
+        }
+        Ok(())
+    }
+
+    // puts a datagram on its way, or loses it
+    fn schedule(&mut self, datagram: Vec<u8>, outgoing: bool) {
+        self.sent += 1;
+        if self.rng.next_f32() < self.conditions.loss {
+            self.dropped += 1;
+            return;
+        }
+        let copies = if self.rng.next_f32() < self.conditions.duplicate { 2 } else { 1 };
+        for _ in 0..copies {
+            let in_flight = InFlight {
+                arrives: self.clock + f64::from(self.delay()),
+                datagram: datagram.clone(),
+            };
+            if outgoing {
+                self.outgoing.push(in_flight);
+            } else {
+                self.incoming.push(in_flight);
+            }
+        }
+    }
+
+    fn delay(&mut self) -> f32 {
+        let conditions = &self.conditions;
+        let mut delay = conditions.latency + self.rng.range(-conditions.jitter, conditions.jitter);
+        if self.rng.next_f32() < conditions.reorder {
+            delay += conditions.reorder_delay;
+        }
+        delay.max(0.0)
+    }
+}
+
+impl<T: Transport> Transport for SimulatedTransport<T> {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        self.schedule(datagram.to_vec(), true);
+        // a perfect link sends straight away
+        self.advance(0.0)
+    }
+
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        while let Some(datagram) = self.inner.recv()? {
+            self.schedule(datagram, false);
+        }
+        let clock = self.clock;
+        let next = self
+            .incoming
+            .iter()
+            .enumerate()
+            .filter(|(_, in_flight)| in_flight.arrives <= clock)
+            .min_by(|(_, a), (_, b)| a.arrives.total_cmp(&b.arrives))
+            .map(|(index, _)| index);
+        Ok(next.map(|index| self.incoming.remove(index).datagram))
+    }
+}
+
+// removes the datagrams that have arrived by `clock`, in the order they arrive
+fn take_due(in_flight: &mut Vec<InFlight>, clock: f64) -> Vec<Vec<u8>> {
+    let mut due: Vec<InFlight> = Vec::new();
+    let mut index = 0;
+    while index < in_flight.len() {
+        if in_flight[index].arrives <= clock {
+            due.push(in_flight.remove(index));
+        } else {
+            index += 1;
+        }
+    }
+    due.sort_by(|a, b| a.arrives.total_cmp(&b.arrives));
+    due.into_iter().map(|in_flight| in_flight.datagram).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    // the far end of a link, in memory, so that only the simulated conditions decide what
+    // arrives when
+    #[derive(Default)]
+    struct Loopback {
+        sent: Vec<Vec<u8>>,
+        arriving: VecDeque<Vec<u8>>,
+    }
+
+    impl Transport for Loopback {
+        fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+            self.sent.push(datagram.to_vec());
+            Ok(())
+        }
+
+        fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+            Ok(self.arriving.pop_front())
+        }
+    }
+
+    fn link(conditions: LinkConditions) -> SimulatedTransport<Loopback> {
+        let mut link = SimulatedTransport::new(Loopback::default(), 3);
+        link.set_conditions(conditions);
+        link
+    }
+
+    fn send_numbered(link: &mut SimulatedTransport<Loopback>, count: u8) {
+        for number in 0..count {
+            link.send(&[number]).unwrap();
+        }
+    }
+
+    fn received(link: &mut SimulatedTransport<Loopback>) -> Vec<Vec<u8>> {
+        std::iter::from_fn(|| link.recv().unwrap()).collect()
+    }
+
+    #[test]
+    fn perfect_link_passes_everything_straight_through() {
+        let mut link = link(LinkConditions::default());
+        send_numbered(&mut link, 3);
+        assert_eq!(link.inner.sent, vec![vec![0], vec![1], vec![2]]);
+        link.inner.arriving.push_back(vec![7]);
+        assert_eq!(received(&mut link), vec![vec![7]]);
+    }
+
+    #[test]
+    fn latency_holds_datagrams_back_both_ways() {
+        let mut link = link(LinkConditions {
+            latency: 0.1,
+            ..LinkConditions::default()
+        });
+        send_numbered(&mut link, 1);
+        link.inner.arriving.push_back(vec![7]);
+        assert!(received(&mut link).is_empty());
+        link.advance(0.05).unwrap();
+        assert!(link.inner.sent.is_empty());
+        assert!(received(&mut link).is_empty());
+
+        link.advance(0.05).unwrap();
+        assert_eq!(link.inner.sent, vec![vec![0]]);
+        assert_eq!(received(&mut link), vec![vec![7]]);
+    }
+
+    #[test]
+    fn total_loss_delivers_nothing() {
+        let mut link = link(LinkConditions {
+            loss: 1.0,
+            ..LinkConditions::default()
+        });
+        send_numbered(&mut link, 10);
+        link.inner.arriving.extend((0..10).map(|number| vec![number]));
+        link.advance(1.0).unwrap();
+        assert!(link.inner.sent.is_empty());
+        assert!(received(&mut link).is_empty());
+        assert_eq!((link.sent(), link.dropped()), (20, 20));
+    }
+
+    #[test]
+    fn duplication_delivers_everything_twice() {
+        let mut link = link(LinkConditions {
+            duplicate: 1.0,
+            ..LinkConditions::default()
+        });
+        send_numbered(&mut link, 3);
+        link.inner.arriving.push_back(vec![7]);
+        assert_eq!(link.inner.sent, vec![vec![0], vec![0], vec![1], vec![1], vec![2], vec![2]]);
+        assert_eq!(received(&mut link), vec![vec![7], vec![7]]);
+    }
+
+    #[test]
+    fn jitter_reorders_but_loses_nothing() {
+        let mut link = link(LinkConditions {
+            latency: 0.1,
+            jitter: 0.05,
+            ..LinkConditions::default()
+        });
+        for number in 0..20 {
+            link.send(&[number]).unwrap();
+            link.advance(0.01).unwrap();
+        }
+        link.advance(1.0).unwrap();
+
+        let numbers: Vec<u8> = link.inner.sent.iter().map(|datagram| datagram[0]).collect();
+        let mut sorted = numbers.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..20).collect::<Vec<u8>>());
+        assert_ne!(numbers, sorted, "nothing was overtaken");
+    }
+
+    #[test]
+    fn reordered_datagrams_are_overtaken() {
+        let mut link = link(LinkConditions {
+            reorder: 0.5,
+            reorder_delay: 0.5,
+            ..LinkConditions::default()
+        });
+        send_numbered(&mut link, 20);
+        let first: Vec<u8> = link.inner.sent.iter().map(|datagram| datagram[0]).collect();
+        assert!(!first.is_empty() && first.len() < 20);
+        link.advance(0.5).unwrap();
+        assert_eq!(link.inner.sent.len(), 20);
+        // those held back come after every one that wasn't
+        let held_back: Vec<u8> = link.inner.sent[first.len()..].iter().map(|datagram| datagram[0]).collect();
+        assert!(held_back.iter().all(|number| !first.contains(number)));
+    }
+
+    #[test]
+    fn same_seed_same_link() {
+        let conditions = LinkConditions {
+            latency: 0.05,
+            jitter: 0.04,
+            loss: 0.3,
+            duplicate: 0.2,
+            reorder: 0.2,
+            reorder_delay: 0.1,
+        };
+        let runs: Vec<Vec<Vec<u8>>> = (0..2)
+            .map(|_| {
+                let mut link = link(conditions.clone());
+                for number in 0..50 {
+                    link.send(&[number]).unwrap();
+                    link.advance(0.01).unwrap();
+                }
+                link.advance(1.0).unwrap();
+                link.inner.sent
+            })
+            .collect();
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[test]
+    fn clock_keeps_milliseconds_after_hours() {
+        let mut link = link(LinkConditions {
+            latency: 0.001,
+            ..LinkConditions::default()
+        });
+        link.advance(10.0 * 3600.0).unwrap();
+        send_numbered(&mut link, 1);
+        assert!(link.inner.sent.is_empty());
+        link.advance(0.001).unwrap();
+        assert_eq!(link.inner.sent, vec![vec![0]]);
+    }
+
+    #[test]
+    fn scenario_phases_run_in_order() {
+        let phase = |name: &str, seconds| Phase {
+            name: name.to_string(),
+            seconds,
+            conditions: LinkConditions::default(),
+        };
+        let scenario = Scenario {
+            phases: vec![phase("calm", 10.0), phase("storm", 5.0)],
+        };
+        assert_eq!(scenario.seconds(), 15.0);
+        assert_eq!(scenario.phase_at(0.0).map(|(index, _)| index), Some(0));
+        assert_eq!(scenario.phase_at(12.0).map(|(_, phase)| phase.name.as_str()), Some("storm"));
+        assert!(scenario.phase_at(15.0).is_none());
+    }
+}
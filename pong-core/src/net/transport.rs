@@ -0,0 +1,110 @@
+//! Moving datagrams between peers.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+// large enough for any message in `protocol`
+const MAX_DATAGRAM: usize = 1500;
+
+/// An unreliable, unordered link to one peer. Messages may be lost, duplicated or arrive out of
+/// order, as with UDP, and the sessions are written to cope with that.
+pub trait Transport {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()>;
+
+    /// The next datagram that has arrived, without blocking. `None` when there is nothing to
+    /// read right now.
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// A non-blocking UDP socket talking to a single peer. Datagrams from any other address are
+/// dropped.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peer: SocketAddr,
+}
+
+impl UdpTransport {
+    pub fn new<A: ToSocketAddrs, B: ToSocketAddrs>(bind: A, peer: B) -> io::Result<UdpTransport> {
+        let peer = peer
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no peer address"))?;
+        UdpTransport::with_socket(UdpSocket::bind(bind)?, peer)
+    }
+
+    /// Two transports on free localhost ports, talking to each other.
+    pub fn pair() -> io::Result<(UdpTransport, UdpTransport)> {
+        let first = UdpSocket::bind("127.0.0.1:0")?;
+        let second = UdpSocket::bind("127.0.0.1:0")?;
+        let (first_addr, second_addr) = (first.local_addr()?, second.local_addr()?);
+        Ok((
+            UdpTransport::with_socket(first, second_addr)?,
+            UdpTransport::with_socket(second, first_addr)?,
+        ))
+    }
+
+    fn with_socket(socket: UdpSocket, peer: SocketAddr) -> io::Result<UdpTransport> {
+        socket.set_nonblocking(true)?;
+        Ok(UdpTransport { socket, peer })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        match self.socket.send_to(datagram, self.peer) {
+            Ok(_) => Ok(()),
+            // the peer isn't listening yet; it will get the next one
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut buffer = [0; MAX_DATAGRAM];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, from)) if from == self.peer => return Ok(Some(buffer[..len].to_vec())),
+                Ok(_) => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // loopback datagrams are handed over at once, but give them a moment all the same
+    fn recv_soon(transport: &mut UdpTransport) -> Option<Vec<u8>> {
+        for _ in 0..100 {
+            if let Some(datagram) = transport.recv().unwrap() {
+                return Some(datagram);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        None
+    }
+
+    #[test]
+    fn pair_passes_datagrams_both_ways() {
+        let (mut first, mut second) = UdpTransport::pair().unwrap();
+        assert_eq!(first.recv().unwrap(), None);
+        first.send(b"ping").unwrap();
+        assert_eq!(recv_soon(&mut second).as_deref(), Some(&b"ping"[..]));
+        second.send(b"pong").unwrap();
+        assert_eq!(recv_soon(&mut first).as_deref(), Some(&b"pong"[..]));
+    }
+
+    #[test]
+    fn strangers_are_ignored() {
+        let (mut first, mut second) = UdpTransport::pair().unwrap();
+        let stranger = UdpSocket::bind("127.0.0.1:0").unwrap();
+        stranger.send_to(b"hi", second.socket.local_addr().unwrap()).unwrap();
+        first.send(b"ping").unwrap();
+        assert_eq!(recv_soon(&mut second).as_deref(), Some(&b"ping"[..]));
+        assert_eq!(second.recv().unwrap(), None);
+    }
+}
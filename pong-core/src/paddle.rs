@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+use crate::timestep::lerp;
+use crate::GameConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Paddle {
+    pub side: Side,
+    pub width: f32,
+    pub height: f32,
+    /// Speed in arena units per second at full axis deflection.
+    pub speed: f32,
+    /// Centre of the paddle in arena coordinates.
+    pub position: [f32; 2],
+    /// Centre of the paddle before the last tick, for interpolated rendering.
+    pub previous_position: [f32; 2],
+    /// Vertical speed over the last tick in arena units per second, positive going up. The
+    /// ball picks some of it up when it hits the paddle.
+    pub velocity: f32,
+}
+
+impl Paddle {
+    /// Creates a paddle flush against its side of the arena, vertically centred.
+    pub fn new(side: Side, config: &GameConfig) -> Paddle {
+        let x = match side {
+            Side::Left => config.paddle_width * 0.5,
+            Side::Right => config.arena_width - config.paddle_width * 0.5,
+        };
+        let position = [x, config.arena_height / 2.0];
+        Paddle {
+            side,
+            width: config.paddle_width,
+            height: config.paddle_height,
+            speed: config.paddle_speed,
+            position,
+            previous_position: position,
+            velocity: 0.0,
+        }
+    }
+
+    /// Takes the size and speed from a changed `config`, staying flush against its side of the
+    /// arena at the same height.
+    pub fn resize(&mut self, config: &GameConfig) {
+        let x = Paddle::new(self.side, config).position[0];
+        self.width = config.paddle_width;
+        self.height = config.paddle_height;
+        self.speed = config.paddle_speed;
+        self.position[0] = x;
+        self.previous_position[0] = x;
+    }
+
+    /// Moves the paddle for `dt` seconds by an input axis value in `-1.0..=1.0`, keeping it
+    /// inside the arena.
+    pub fn apply_input(&mut self, movement: f32, arena_height: f32, dt: f32) {
+        self.previous_position = self.position;
+        let scaled_amount = self.speed * movement * dt;
+        self.position[1] = (self.position[1] + scaled_amount)
+            .min(arena_height - self.height * 0.5)
+            .max(self.height * 0.5);
+        // measured after clamping, so a paddle pushing against a wall isn't moving
+        self.velocity = (self.position[1] - self.previous_position[1]) / dt;
+    }
+
+    /// Where to draw the paddle when the clock is `alpha` of the way to the next tick.
+    pub fn interpolated_position(&self, alpha: f32) -> [f32; 2] {
+        lerp(self.previous_position, self.position, alpha)
+    }
+}
@@ -0,0 +1,418 @@
+//! Drawing matches without a GPU.
+//!
+//! `Renderer` rasterizes an `Arena` on the CPU with the same sprites and clear color the game
+//! draws with, so a recorded or simulated match can be turned into images anywhere, e.g. for a
+//! bug report. The camera shows the whole arena, as in the game, and the score is drawn with
+//! blocky digits where the game shows its score text.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+use crate::Arena;
+
+/// The color the game clears its window to, in linear RGBA.
+pub const CLEAR_COLOR: [f32; 4] = [0.00196, 0.23726, 0.21765, 1.0];
+
+const SCORE_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+// 3x5 digits, one byte per row with the leftmost column in bit 2
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Contents of a sprite sheet RON file such as `assets/texture/pong_spritesheet.ron`, in the
+/// `List` format amethyst loads.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum SpriteSheet {
+    List(SpriteList),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SpriteList {
+    /// Size of the texture the sprite positions are measured in.
+    pub texture_width: u32,
+    pub texture_height: u32,
+    pub sprites: Vec<SpritePosition>,
+}
+
+/// A sprite's rectangle in the texture, in pixels from the top left.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct SpritePosition {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An 8-bit RGBA image, row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// An image filled with `color`.
+    pub fn new(width: u32, height: u32, color: [u8; 4]) -> Image {
+        Image {
+            width,
+            height,
+            pixels: color.repeat(width as usize * height as usize),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = self.index(x, y);
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+
+    // draws `color` over the pixel at `x`, `y`, letting through as much as its alpha leaves
+    fn blend(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        let i = self.index(x, y);
+        let alpha = u32::from(color[3]);
+        for (channel, &over) in color.iter().take(3).enumerate() {
+            let under = u32::from(self.pixels[i + channel]);
+            self.pixels[i + channel] = ((u32::from(over) * alpha + under * (255 - alpha)) / 255) as u8;
+        }
+        let under = u32::from(self.pixels[i + 3]);
+        self.pixels[i + 3] = (alpha + under * (255 - alpha) / 255) as u8;
+    }
+
+    // the pixels whose centres lie inside `rect`, clipped to the image, as (x, y) ranges
+    fn covered(&self, rect: Rect) -> (std::ops::Range<u32>, std::ops::Range<u32>) {
+        let range = |from: f32, to: f32, size: u32| {
+            let first = (from - 0.5).ceil().max(0.0) as u32;
+            let end = ((to - 0.5).ceil().max(0.0) as u32).min(size);
+            first..end.max(first)
+        };
+        (range(rect.left, rect.right, self.width), range(rect.top, rect.bottom, self.height))
+    }
+
+    fn fill(&mut self, rect: Rect, color: [u8; 4]) {
+        let (columns, rows) = self.covered(rect);
+        for y in rows {
+            for x in columns.clone() {
+                self.blend(x, y, color);
+            }
+        }
+    }
+
+    // stretches `source` over `rect`, picking the nearest texel for every pixel
+    fn draw(&mut self, texture: &Image, source: Rect, rect: Rect) {
+        let (columns, rows) = self.covered(rect);
+        for y in rows {
+            let v = (y as f32 + 0.5 - rect.top) / (rect.bottom - rect.top);
+            let texel_y = source.top + v * (source.bottom - source.top);
+            let texel_y = (texel_y as u32).min(texture.height - 1);
+            for x in columns.clone() {
+                let u = (x as f32 + 0.5 - rect.left) / (rect.right - rect.left);
+                let texel_x = source.left + u * (source.right - source.left);
+                let texel_x = (texel_x as u32).min(texture.width - 1);
+                self.blend(x, y, texture.pixel(texel_x, texel_y));
+            }
+        }
+    }
+}
+
+// a rectangle in pixels, with y growing downwards
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    /// The sprite sheet has no sprite with this index.
+    MissingSprite(usize),
+    /// The sprite with this index reaches outside the texture.
+    SpriteOutOfBounds(usize),
+    /// The texture, or the image asked for, has no pixels.
+    Empty,
+    /// The texture has this many bytes of pixels, which isn't 4 for each of them.
+    TextureSize(usize),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::MissingSprite(index) => write!(f, "the sprite sheet has no sprite {}", index),
+            RenderError::SpriteOutOfBounds(index) => write!(f, "sprite {} reaches outside the texture", index),
+            RenderError::Empty => write!(f, "the image has no pixels"),
+            RenderError::TextureSize(len) => write!(f, "the texture's {} bytes don't match its size", len),
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+/// Draws arenas into images of a fixed size.
+pub struct Renderer {
+    texture: Image,
+    paddle: Rect,
+    ball: Rect,
+    width: u32,
+    height: u32,
+    clear: [u8; 4],
+}
+
+impl Renderer {
+    /// Draws with the sprites of `sheet` cut from `texture`: the paddle is the first sprite and
+    /// the ball the second, as in the game. The images are `width` x `height` pixels.
+    pub fn new(sheet: &SpriteSheet, texture: Image, width: u32, height: u32) -> Result<Renderer, RenderError> {
+        if texture.width == 0 || texture.height == 0 || width == 0 || height == 0 {
+            return Err(RenderError::Empty);
+        }
+        // `Image`'s fields are public, so nothing else makes sure they agree
+        if texture.pixels.len() != texture.width as usize * texture.height as usize * 4 {
+            return Err(RenderError::TextureSize(texture.pixels.len()));
+        }
+        let SpriteSheet::List(list) = sheet;
+        // the texture may not be the size the sheet was made for; amethyst scales the
+        // positions to it
+        let sprite = |index: usize| {
+            let sprite = list.sprites.get(index).ok_or(RenderError::MissingSprite(index))?;
+            let right = u64::from(sprite.x) + u64::from(sprite.width);
+            let bottom = u64::from(sprite.y) + u64::from(sprite.height);
+            if right > u64::from(list.texture_width) || bottom > u64::from(list.texture_height) {
+                return Err(RenderError::SpriteOutOfBounds(index));
+            }
+            let scale_x = texture.width as f32 / list.texture_width as f32;
+            let scale_y = texture.height as f32 / list.texture_height as f32;
+            Ok(Rect {
+                left: sprite.x as f32 * scale_x,
+                top: sprite.y as f32 * scale_y,
+                right: (sprite.x + sprite.width) as f32 * scale_x,
+                bottom: (sprite.y + sprite.height) as f32 * scale_y,
+            })
+        };
+        Ok(Renderer {
+            paddle: sprite(0)?,
+            ball: sprite(1)?,
+            texture,
+            width,
+            height,
+            clear: srgb(CLEAR_COLOR),
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Draws the paddles, the ball and the score as they are at the end of the arena's last
+    /// tick.
+    pub fn render(&self, arena: &Arena) -> Image {
+        let mut image = Image::new(self.width, self.height, self.clear);
+        let (scale_x, scale_y) = (self.width as f32 / arena.width, self.height as f32 / arena.height);
+        // arena units from the bottom left to pixels from the top left
+        let rect = |[x, y]: [f32; 2], width: f32, height: f32| Rect {
+            left: (x - width * 0.5) * scale_x,
+            top: (arena.height - y - height * 0.5) * scale_y,
+            right: (x + width * 0.5) * scale_x,
+            bottom: (arena.height - y + height * 0.5) * scale_y,
+        };
+
+        for paddle in [&arena.left, &arena.right].iter() {
+            let to = rect(paddle.position, paddle.width, paddle.height);
+            image.draw(&self.texture, self.paddle, to);
+        }
+        let ball = &arena.ball;
+        image.draw(&self.texture, self.ball, rect(ball.position, ball.radius * 2.0, ball.radius * 2.0));
+
+        // where the game puts its score text: 50 pixels either side of the middle and 50 down
+        // in its 500 pixel window
+        let digit_height = self.height as f32 * 0.08;
+        let top = self.height as f32 * 0.1;
+        let middle = self.width as f32 * 0.5;
+        let offset = self.width as f32 * 0.1;
+        draw_number(&mut image, arena.scores.score_left, middle - offset, top, digit_height);
+        draw_number(&mut image, arena.scores.score_right, middle + offset, top, digit_height);
+        image
+    }
+}
+
+// draws `value` centred on `centre`, with digits `height` pixels tall
+fn draw_number(image: &mut Image, value: i32, centre: f32, top: f32, height: f32) {
+    let digits: Vec<usize> = value
+        .to_string()
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .map(|b| (b - b'0') as usize)
+        .collect();
+    let block = height / 5.0;
+    // digits are three blocks wide with one block between them
+    let width = (digits.len() * 4 - 1) as f32 * block;
+    let mut left = centre - width * 0.5;
+    for digit in digits {
+        for (row, bits) in DIGITS[digit].iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    let x = left + column as f32 * block;
+                    let y = top + row as f32 * block;
+                    let rect = Rect {
+                        left: x,
+                        top: y,
+                        right: x + block,
+                        bottom: y + block,
+                    };
+                    image.fill(rect, SCORE_COLOR);
+                }
+            }
+        }
+        left += block * 4.0;
+    }
+}
+
+// the window's framebuffer is sRGB, so the linear clear color is stored encoded
+fn srgb(linear: [f32; 4]) -> [u8; 4] {
+    let encode = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        let encoded = if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (encoded * 255.0).round() as u8
+    };
+    [encode(linear[0]), encode(linear[1]), encode(linear[2]), (linear[3].clamp(0.0, 1.0) * 255.0).round() as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PADDLE: [u8; 4] = [255, 0, 0, 255];
+    const BALL: [u8; 4] = [0, 0, 255, 255];
+
+    // like the game's sheet: the paddle sprite on the left, the ball on the right
+    fn sheet(sprites: Vec<SpritePosition>) -> SpriteSheet {
+        SpriteSheet::List(SpriteList {
+            texture_width: 8,
+            texture_height: 4,
+            sprites,
+        })
+    }
+
+    fn sprite(x: u32, width: u32) -> SpritePosition {
+        SpritePosition { x, y: 0, width, height: 4 }
+    }
+
+    fn texture() -> Image {
+        let mut texture = Image::new(8, 4, PADDLE);
+        for y in 0..4 {
+            for x in 4..8 {
+                texture.blend(x, y, BALL);
+            }
+        }
+        texture
+    }
+
+    // one pixel per arena unit
+    fn renderer() -> Renderer {
+        Renderer::new(&sheet(vec![sprite(0, 4), sprite(4, 4)]), texture(), 100, 100).unwrap()
+    }
+
+    fn clear() -> [u8; 4] {
+        srgb(CLEAR_COLOR)
+    }
+
+    #[test]
+    fn empty_arena_is_clear_apart_from_the_score() {
+        let mut arena = Arena::default();
+        for paddle in [&mut arena.left, &mut arena.right].iter_mut() {
+            paddle.position = [-50.0, -50.0];
+        }
+        arena.ball.position = [500.0, 500.0];
+        let image = renderer().render(&arena);
+
+        assert_eq!((image.width, image.height, image.pixels.len()), (100, 100, 100 * 100 * 4));
+        for y in 0..100 {
+            for x in 0..100 {
+                let pixel = image.pixel(x, y);
+                // the score sits in the top fifth
+                let allowed = pixel == clear() || (y < 20 && pixel == SCORE_COLOR);
+                assert!(allowed, "{:?} at {}, {}", pixel, x, y);
+            }
+        }
+        assert!(image.pixels.chunks(4).any(|pixel| pixel == SCORE_COLOR));
+    }
+
+    #[test]
+    fn paddles_and_ball_are_drawn_with_y_up() {
+        let mut arena = Arena::default();
+        arena.left.position[1] = 30.0;
+        arena.right.position[1] = 70.0;
+        arena.ball.position = [50.0, 80.0];
+        let image = renderer().render(&arena);
+
+        let left_x = arena.left.position[0] as u32;
+        let right_x = arena.right.position[0] as u32;
+        assert_eq!(image.pixel(left_x, 70), PADDLE);
+        assert_eq!(image.pixel(left_x, 30), clear());
+        assert_eq!(image.pixel(right_x, 30), PADDLE);
+        assert_eq!(image.pixel(right_x, 70), clear());
+        // the paddle's height is 16 units
+        assert_eq!(image.pixel(left_x, 70 - 7), PADDLE);
+        assert_eq!(image.pixel(left_x, 70 - 9), clear());
+        assert_eq!(image.pixel(50, 20), BALL);
+        assert_eq!(image.pixel(50, 80), clear());
+    }
+
+    #[test]
+    fn scores_are_drawn_as_digits() {
+        let renderer = Renderer::new(&sheet(vec![sprite(0, 4), sprite(4, 4)]), texture(), 500, 500).unwrap();
+        let mut arena = Arena::default();
+        // the middle block of the left digit's middle row is only lit for 8 of these
+        let middle = |image: &Image| image.pixel(200, 70);
+        arena.scores.score_left = 0;
+        assert_eq!(middle(&renderer.render(&arena)), clear());
+        arena.scores.score_left = 8;
+        let eight = renderer.render(&arena);
+        assert_eq!(middle(&eight), SCORE_COLOR);
+        // its top row is lit all the way across
+        for x in [189, 200, 211] {
+            assert_eq!(eight.pixel(x, 54), SCORE_COLOR, "{}", x);
+        }
+
+        // two digits are centred on the same spot, so the 1 of 10 is left of it
+        arena.scores.score_left = 10;
+        let ten = renderer.render(&arena);
+        assert_eq!(ten.pixel(182, 54), SCORE_COLOR);
+        assert_eq!(ten.pixel(200, 54), clear());
+    }
+
+    #[test]
+    fn broken_sheets_and_textures_are_reported() {
+        let missing = Renderer::new(&sheet(vec![sprite(0, 4)]), texture(), 10, 10);
+        assert!(matches!(missing, Err(RenderError::MissingSprite(1))));
+        let outside = Renderer::new(&sheet(vec![sprite(6, 4), sprite(4, 4)]), texture(), 10, 10);
+        assert!(matches!(outside, Err(RenderError::SpriteOutOfBounds(0))));
+        let huge = Renderer::new(&sheet(vec![sprite(0, 4), sprite(4, u32::MAX)]), texture(), 10, 10);
+        assert!(matches!(huge, Err(RenderError::SpriteOutOfBounds(1))));
+
+        let mut short = texture();
+        short.pixels.truncate(10);
+        let short = Renderer::new(&sheet(vec![sprite(0, 4), sprite(4, 4)]), short, 10, 10);
+        assert!(matches!(short, Err(RenderError::TextureSize(10))));
+        let empty = Renderer::new(&sheet(vec![sprite(0, 4), sprite(4, 4)]), texture(), 0, 10);
+        assert!(matches!(empty, Err(RenderError::Empty)));
+    }
+}
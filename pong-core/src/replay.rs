@@ -0,0 +1,259 @@
+//! Recorded matches.
+//!
+//! A match is fully determined by its configuration, the seed its serves are drawn from and the
+//! input of every tick, so that is all a replay stores. Played back through `ReplayController`
+//! on the same configuration it reproduces the match exactly.
+//!
+//! The file format is little-endian: the magic bytes `PONGRPLY`, a `u16` format version, the
+//! `u64` seed, the `u64` configuration hash and a `u32` tick count, then per tick the left and
+//! right movement as `f32` and a byte of serve flags (bit 0 left, bit 1 right).
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::controller::{ControlContext, PaddleController};
+use crate::hash::Fnv;
+use crate::{GameConfig, Input, MatchRules, PhysicsConfig, Side};
+
+const MAGIC: &[u8; 8] = b"PONGRPLY";
+/// Format version written by this build. Files with any other version are rejected.
+pub const VERSION: u16 = 1;
+
+// most ticks space is made for up front when reading a replay
+const MAX_RESERVED_TICKS: usize = 1 << 19;
+
+const SERVE_LEFT: u8 = 1;
+const SERVE_RIGHT: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    /// `config_hash` of the configuration the match was played with.
+    pub config_hash: u64,
+    /// Input of every tick, in order.
+    pub inputs: Vec<Input>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// The file doesn't start with the replay magic bytes.
+    NotAReplay,
+    UnsupportedVersion(u16),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "{}", e),
+            ReplayError::NotAReplay => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay format version {} is not supported, expected {}",
+                version, VERSION
+            ),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> ReplayError {
+        ReplayError::Io(e)
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64, config_hash: u64) -> Replay {
+        Replay {
+            seed,
+            config_hash,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.config_hash.to_le_bytes())?;
+        writer.write_all(&(self.inputs.len() as u32).to_le_bytes())?;
+        for input in &self.inputs {
+            let mut flags = 0;
+            if input.serve_left {
+                flags |= SERVE_LEFT;
+            }
+            if input.serve_right {
+                flags |= SERVE_RIGHT;
+            }
+            writer.write_all(&input.left.to_bits().to_le_bytes())?;
+            writer.write_all(&input.right.to_bits().to_le_bytes())?;
+            writer.write_all(&[flags])?;
+        }
+        writer.flush()
+    }
+
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Replay, ReplayError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let seed = read_u64(&mut reader)?;
+        let config_hash = read_u64(&mut reader)?;
+        let ticks = read_u32(&mut reader)?;
+        // the count comes from the file, so a corrupt one mustn't decide how much is allocated
+        // before the ticks are actually there; an hour at 120 Hz fits in the first allocation
+        let mut inputs = Vec::with_capacity((ticks as usize).min(MAX_RESERVED_TICKS));
+        for _ in 0..ticks {
+            let left = f32::from_bits(read_u32(&mut reader)?);
+            let right = f32::from_bits(read_u32(&mut reader)?);
+            let mut flags = [0; 1];
+            reader.read_exact(&mut flags)?;
+            inputs.push(Input {
+                left,
+                right,
+                serve_left: flags[0] & SERVE_LEFT != 0,
+                serve_right: flags[0] & SERVE_RIGHT != 0,
+            });
+        }
+
+        Ok(Replay {
+            seed,
+            config_hash,
+            inputs,
+        })
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// A fingerprint of every setting that affects how a match plays out. A replay only plays back
+/// faithfully on a configuration with the same hash.
+pub fn config_hash(game: &GameConfig, physics: &PhysicsConfig, rules: &MatchRules) -> u64 {
+    // the instant replay only changes what is shown between points, not the match itself
+    let rules = MatchRules {
+        instant_replay: MatchRules::default().instant_replay,
+        ..rules.clone()
+    };
+    // hash the debug output, which prints every field and the exact value of each float
+    let mut hash = Fnv::default();
+    hash.write(format!("{:?}{:?}{:?}", game, physics, rules).as_bytes());
+    hash.finish()
+}
+
+/// Plays back one side of a recorded match, a tick at a time. Once the recording runs out the
+/// paddle stands still.
+pub struct ReplayController {
+    inputs: Vec<Input>,
+    side: Side,
+    tick: usize,
+}
+
+impl ReplayController {
+    pub fn new(inputs: Vec<Input>, side: Side) -> ReplayController {
+        ReplayController { inputs, side, tick: 0 }
+    }
+}
+
+impl PaddleController for ReplayController {
+    fn movement(&mut self, _context: &ControlContext<'_>) -> f32 {
+        let movement = self.inputs.get(self.tick).map_or(0.0, |input| input.for_side(self.side));
+        self.tick += 1;
+        movement
+    }
+
+    fn serve(&mut self, _context: &ControlContext<'_>) -> bool {
+        self.inputs.get(self.tick).is_some_and(|input| input.serve(self.side))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        let mut replay = Replay::new(42, 0xdead_beef_0123_4567);
+        replay.inputs = vec![
+            Input::default(),
+            Input {
+                left: 1.0,
+                right: -0.25,
+                serve_left: true,
+                serve_right: false,
+            },
+            Input {
+                left: -1.0,
+                right: 0.5,
+                serve_left: false,
+                serve_right: true,
+            },
+        ];
+        replay
+    }
+
+    fn written(replay: &Replay) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        replay.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = replay();
+        let bytes = written(&replay);
+        assert_eq!(bytes.len(), 8 + 2 + 8 + 8 + 4 + 3 * 9);
+        assert_eq!(Replay::read_from(&bytes[..]).unwrap(), replay);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = written(&replay());
+        bytes[0] = b'X';
+        assert!(matches!(Replay::read_from(&bytes[..]), Err(ReplayError::NotAReplay)));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut bytes = written(&replay());
+        bytes[8..10].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            Replay::read_from(&bytes[..]),
+            Err(ReplayError::UnsupportedVersion(version)) if version == VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = written(&replay());
+        for len in [0, 5, 20, bytes.len() - 1] {
+            assert!(matches!(Replay::read_from(&bytes[..len]), Err(ReplayError::Io(_))), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn huge_tick_count_fails_without_reserving_it() {
+        let mut bytes = written(&Replay::new(1, 2));
+        bytes[26..30].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(Replay::read_from(&bytes[..]), Err(ReplayError::Io(_))));
+    }
+}
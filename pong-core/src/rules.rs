@@ -0,0 +1,139 @@
+//! When a match is over.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ScoreBoard, ServeRule, Side};
+
+/// Contents of `config/rules.ron`. Missing fields fall back to their defaults.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MatchRules {
+    /// Points needed to win.
+    pub target_score: i32,
+    /// How far ahead the winner has to be, 2 for the classic win-by-two.
+    pub win_by: i32,
+    /// Seconds after which whoever leads wins. A tie at the limit goes to the next point.
+    pub time_limit: Option<f32>,
+    /// Who serves after each point.
+    pub serve_rule: ServeRule,
+    /// Seconds the ball is held before it is served automatically.
+    pub serve_delay: f32,
+    /// Serves leave at a random angle up to this many degrees either side of horizontal.
+    pub max_serve_angle: f32,
+    /// Replays the lead-up to every point in slow motion before the next serve.
+    pub instant_replay: bool,
+}
+
+impl Default for MatchRules {
+    fn default() -> MatchRules {
+        MatchRules {
+            target_score: 11,
+            win_by: 2,
+            time_limit: None,
+            serve_rule: ServeRule::Alternate,
+            serve_delay: 2.0,
+            max_serve_angle: 30.0,
+            instant_replay: true,
+        }
+    }
+}
+
+impl MatchRules {
+    /// The side that has won with these scores after `elapsed` seconds, if any.
+    pub fn winner(&self, scores: &ScoreBoard, elapsed: f32) -> Option<Side> {
+        let lead = scores.score_left - scores.score_right;
+        let leader = if lead > 0 {
+            Side::Left
+        } else if lead < 0 {
+            Side::Right
+        } else {
+            return None;
+        };
+
+        let reached_target = scores.score(leader) >= self.target_score && lead.abs() >= self.win_by;
+        let out_of_time = self.time_limit.is_some_and(|limit| elapsed >= limit);
+        if reached_target || out_of_time {
+            Some(leader)
+        } else {
+            None
+        }
+    }
+}
+
+/// Progress of the current match.
+#[derive(Clone, Debug, Default)]
+pub struct MatchState {
+    /// Seconds played.
+    pub elapsed: f32,
+    pub winner: Option<Side>,
+}
+
+impl MatchState {
+    /// Counts `dt` seconds of play and checks whether someone has won. Returns the winner on
+    /// the tick the match ends.
+    pub fn update(&mut self, rules: &MatchRules, scores: &ScoreBoard, dt: f32) -> Option<Side> {
+        if self.winner.is_some() {
+            return None;
+        }
+        self.elapsed += dt;
+        self.winner = rules.winner(scores, self.elapsed);
+        self.winner
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(left: i32, right: i32) -> ScoreBoard {
+        ScoreBoard {
+            score_left: left,
+            score_right: right,
+        }
+    }
+
+    #[test]
+    fn winner_needs_the_target_score() {
+        let rules = MatchRules::default();
+        assert_eq!(rules.winner(&scores(10, 3), 0.0), None);
+        assert_eq!(rules.winner(&scores(11, 3), 0.0), Some(Side::Left));
+        assert_eq!(rules.winner(&scores(4, 11), 0.0), Some(Side::Right));
+    }
+
+    #[test]
+    fn winner_has_to_win_by_two() {
+        let rules = MatchRules::default();
+        assert_eq!(rules.winner(&scores(11, 10), 0.0), None);
+        assert_eq!(rules.winner(&scores(12, 11), 0.0), None);
+        assert_eq!(rules.winner(&scores(13, 11), 0.0), Some(Side::Left));
+        assert_eq!(rules.winner(&scores(12, 14), 0.0), Some(Side::Right));
+    }
+
+    #[test]
+    fn time_limit_goes_to_the_leader() {
+        let rules = MatchRules {
+            time_limit: Some(60.0),
+            ..MatchRules::default()
+        };
+        assert_eq!(rules.winner(&scores(3, 2), 59.9), None);
+        assert_eq!(rules.winner(&scores(3, 2), 60.0), Some(Side::Left));
+        assert_eq!(rules.winner(&scores(0, 1), 75.0), Some(Side::Right));
+        // a tie plays on to the next point
+        assert_eq!(rules.winner(&scores(2, 2), 75.0), None);
+    }
+
+    #[test]
+    fn state_keeps_the_first_winner() {
+        let rules = MatchRules::default();
+        let mut state = MatchState::default();
+        assert_eq!(state.update(&rules, &scores(11, 0), 0.5), Some(Side::Left));
+        assert!(state.is_over());
+        assert_eq!(state.update(&rules, &scores(11, 20), 0.5), None);
+        assert_eq!(state.winner, Some(Side::Left));
+        assert_eq!(state.elapsed, 0.5);
+    }
+}
@@ -0,0 +1,85 @@
+//! Scoring when the ball leaves the arena.
+
+use crate::{Ball, Side};
+
+/// The points each side has won so far.
+#[derive(Clone, Debug, Default)]
+pub struct ScoreBoard {
+    pub score_left: i32,
+    pub score_right: i32,
+}
+
+impl ScoreBoard {
+    pub fn score(&self, side: Side) -> i32 {
+        match side {
+            Side::Left => self.score_left,
+            Side::Right => self.score_right,
+        }
+    }
+}
+
+/// Awards a point if the ball has reached the left or right edge, then holds the ball in the
+/// centre for the next serve. Returns the side that won the point.
+pub fn check_point(
+    ball: &mut Ball,
+    arena_width: f32,
+    arena_height: f32,
+    scores: &mut ScoreBoard,
+) -> Option<Side> {
+    let ball_x = ball.position[0];
+
+    let winner = if ball_x <= ball.radius {
+        scores.score_right += 1;
+        Some(Side::Right)
+    } else if ball_x >= arena_width - ball.radius {
+        scores.score_left += 1;
+        Some(Side::Left)
+    } else {
+        None
+    };
+
+    if winner.is_some() {
+        ball.hold([arena_width / 2.0, arena_height / 2.0]);
+    }
+    winner
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameConfig;
+
+    fn ball_at(x: f32) -> Ball {
+        let mut ball = Ball::new(&GameConfig::default(), 86.0);
+        ball.teleport([x, 30.0]);
+        ball
+    }
+
+    #[test]
+    fn ball_past_the_left_edge_is_a_point_for_the_right() {
+        let mut ball = ball_at(1.0);
+        let mut scores = ScoreBoard::default();
+        assert_eq!(check_point(&mut ball, 100.0, 100.0, &mut scores), Some(Side::Right));
+        assert_eq!((scores.score_left, scores.score_right), (0, 1));
+        assert_eq!(ball.position, [50.0, 50.0]);
+        assert_eq!(ball.speed, 0.0);
+    }
+
+    #[test]
+    fn ball_past_the_right_edge_is_a_point_for_the_left() {
+        let mut ball = ball_at(99.0);
+        let mut scores = ScoreBoard::default();
+        assert_eq!(check_point(&mut ball, 100.0, 100.0, &mut scores), Some(Side::Left));
+        assert_eq!((scores.score_left, scores.score_right), (1, 0));
+        assert_eq!(ball.position, [50.0, 50.0]);
+    }
+
+    #[test]
+    fn ball_in_play_is_no_point() {
+        let mut ball = ball_at(50.0);
+        let mut scores = ScoreBoard::default();
+        assert_eq!(check_point(&mut ball, 100.0, 100.0, &mut scores), None);
+        assert_eq!((scores.score_left, scores.score_right), (0, 0));
+        assert_eq!(ball.speed, 86.0);
+    }
+}
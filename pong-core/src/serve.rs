@@ -0,0 +1,175 @@
+//! Who serves, and when the ball is launched.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Ball, MatchRules, PhysicsConfig, Rng, Side};
+
+/// Who serves after a point is scored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ServeRule {
+    /// The sides take turns.
+    #[default]
+    Alternate,
+    /// The side that lost the point serves.
+    Loser,
+}
+
+/// The serve phase. Between points the ball is held in the middle of the arena until the
+/// countdown runs out or the server presses serve, then it is launched towards the receiver.
+#[derive(Clone, Debug)]
+pub struct Serve {
+    pub server: Side,
+    /// Seconds left before the ball launches by itself, `None` while the ball is in play.
+    pub countdown: Option<f32>,
+    rng: Rng,
+}
+
+impl Serve {
+    /// Starts the first serve of a match. The first server is drawn from `seed`, and so are
+    /// the serve angles, so the same seed always serves the same way.
+    pub fn new(seed: u64, rules: &MatchRules) -> Serve {
+        let mut rng = Rng::new(seed);
+        let server = if rng.next_f32() < 0.5 { Side::Left } else { Side::Right };
+        Serve {
+            server,
+            countdown: Some(rules.serve_delay),
+            rng,
+        }
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.countdown.is_some()
+    }
+
+    /// Picks the next server after `scorer` won a point and starts the countdown.
+    pub fn after_point(&mut self, scorer: Side, rules: &MatchRules) {
+        self.server = match rules.serve_rule {
+            ServeRule::Alternate => self.server.other(),
+            ServeRule::Loser => scorer.other(),
+        };
+        self.countdown = Some(rules.serve_delay);
+    }
+
+    /// Counts down `dt` seconds of the serve phase. The ball is launched when the countdown
+    /// ends or `pressed` is set. Returns true on the tick the ball is served.
+    pub fn update(
+        &mut self,
+        ball: &mut Ball,
+        pressed: bool,
+        rules: &MatchRules,
+        physics: &PhysicsConfig,
+        dt: f32,
+    ) -> bool {
+        let countdown = match self.countdown {
+            Some(countdown) => countdown - dt,
+            None => return false,
+        };
+        if countdown > 0.0 && !pressed {
+            self.countdown = Some(countdown);
+            return false;
+        }
+
+        self.countdown = None;
+        let angle = self.rng.range(-rules.max_serve_angle, rules.max_serve_angle).to_radians();
+        let towards = match self.server {
+            Side::Left => 1.0,
+            Side::Right => -1.0,
+        };
+        ball.direction = [towards * angle.cos(), angle.sin()];
+        ball.speed = physics.ball_speed;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameConfig;
+
+    fn rules(serve_rule: ServeRule) -> MatchRules {
+        MatchRules {
+            serve_rule,
+            serve_delay: 0.5,
+            ..MatchRules::default()
+        }
+    }
+
+    fn ball() -> Ball {
+        Ball::new(&GameConfig::default(), 0.0)
+    }
+
+    #[test]
+    fn the_ball_launches_when_the_countdown_ends() {
+        let (rules, physics) = (rules(ServeRule::Alternate), PhysicsConfig::default());
+        let (mut serve, mut ball) = (Serve::new(1, &rules), ball());
+        assert!(!serve.update(&mut ball, false, &rules, &physics, 0.25));
+        assert_eq!(serve.countdown, Some(0.25));
+        assert_eq!(ball.speed, 0.0);
+        assert!(serve.update(&mut ball, false, &rules, &physics, 0.25));
+        assert!(!serve.is_waiting());
+        assert_eq!(ball.speed, physics.ball_speed);
+        // nothing more to do until the next point
+        assert!(!serve.update(&mut ball, true, &rules, &physics, 0.25));
+    }
+
+    #[test]
+    fn pressing_serve_skips_the_countdown() {
+        let (rules, physics) = (rules(ServeRule::Alternate), PhysicsConfig::default());
+        let (mut serve, mut ball) = (Serve::new(1, &rules), ball());
+        assert!(serve.update(&mut ball, true, &rules, &physics, 0.01));
+        assert_eq!(serve.countdown, None);
+    }
+
+    #[test]
+    fn alternate_takes_turns_whoever_scores() {
+        let rules = rules(ServeRule::Alternate);
+        let mut serve = Serve::new(1, &rules);
+        serve.countdown = None;
+        let first = serve.server;
+        serve.after_point(Side::Left, &rules);
+        assert_eq!(serve.server, first.other());
+        assert_eq!(serve.countdown, Some(rules.serve_delay));
+        serve.after_point(Side::Left, &rules);
+        assert_eq!(serve.server, first);
+    }
+
+    #[test]
+    fn loser_serves_after_losing_the_point() {
+        let rules = rules(ServeRule::Loser);
+        let mut serve = Serve::new(1, &rules);
+        serve.after_point(Side::Left, &rules);
+        assert_eq!(serve.server, Side::Right);
+        serve.after_point(Side::Left, &rules);
+        assert_eq!(serve.server, Side::Right);
+        serve.after_point(Side::Right, &rules);
+        assert_eq!(serve.server, Side::Left);
+    }
+
+    #[test]
+    fn serves_go_to_the_receiver_within_the_max_angle() {
+        let (rules, physics) = (rules(ServeRule::Alternate), PhysicsConfig::default());
+        for seed in 0..200 {
+            let (mut serve, mut ball) = (Serve::new(seed, &rules), ball());
+            let server = serve.server;
+            serve.update(&mut ball, true, &rules, &physics, 0.01);
+            let towards_receiver = match server {
+                Side::Left => ball.direction[0] > 0.0,
+                Side::Right => ball.direction[0] < 0.0,
+            };
+            assert!(towards_receiver, "seed {}", seed);
+            assert!(ball.heading().abs() <= rules.max_serve_angle + 1e-3, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_serves_the_same_way() {
+        let (rules, physics) = (rules(ServeRule::Alternate), PhysicsConfig::default());
+        let serve = |seed| {
+            let (mut serve, mut ball) = (Serve::new(seed, &rules), ball());
+            serve.update(&mut ball, true, &rules, &physics, 0.01);
+            (serve.server, ball.direction)
+        };
+        assert_eq!(serve(9), serve(9));
+        assert_ne!(serve(9).1, serve(10).1);
+    }
+}
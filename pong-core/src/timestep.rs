@@ -0,0 +1,100 @@
+//! Fixed-rate physics ticks driven by a variable frame time.
+
+/// Most ticks run for a single frame. A long hitch drops the remaining time instead of trying
+/// to catch up, which would make the next frame even slower.
+pub const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Accumulates frame time and hands it out in fixed ticks.
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    tick_seconds: f32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    /// Creates a clock that ticks `tick_rate` times per second.
+    pub fn new(tick_rate: u32) -> FixedTimestep {
+        FixedTimestep {
+            tick_seconds: 1.0 / tick_rate.max(1) as f32,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Length of one tick in seconds. This is the `dt` every physics step uses.
+    pub fn tick_seconds(&self) -> f32 {
+        self.tick_seconds
+    }
+
+    /// Adds a frame's worth of time and returns how many ticks to run for it.
+    pub fn advance(&mut self, frame_seconds: f32) -> u32 {
+        self.accumulator += frame_seconds;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_seconds {
+            self.accumulator -= self.tick_seconds;
+            ticks += 1;
+            if ticks == MAX_TICKS_PER_FRAME {
+                self.accumulator = 0.0;
+                break;
+            }
+        }
+        ticks
+    }
+
+    /// How far the clock is between the last tick and the next one, in `0.0..1.0`. Rendering
+    /// uses this to blend the previous and current positions.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.tick_seconds
+    }
+}
+
+/// Linear interpolation between two positions.
+pub fn lerp(from: [f32; 2], to: [f32; 2], alpha: f32) -> [f32; 2] {
+    [
+        from[0] + (to[0] - from[0]) * alpha,
+        from[1] + (to[1] - from[1]) * alpha,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // quarter-second ticks, so every time below is exact in binary
+    fn clock() -> FixedTimestep {
+        FixedTimestep::new(4)
+    }
+
+    #[test]
+    fn leftover_time_carries_over_to_the_next_frame() {
+        let mut clock = clock();
+        assert_eq!(clock.advance(0.125), 0);
+        assert_eq!(clock.alpha(), 0.5);
+        assert_eq!(clock.advance(0.375), 2);
+        assert_eq!(clock.alpha(), 0.0);
+        assert_eq!(clock.advance(0.3125), 1);
+        assert_eq!(clock.alpha(), 0.25);
+    }
+
+    #[test]
+    fn a_long_frame_runs_at_most_max_ticks_and_drops_the_rest() {
+        let mut clock = clock();
+        assert_eq!(clock.advance(100.0), MAX_TICKS_PER_FRAME);
+        assert_eq!(clock.alpha(), 0.0);
+        // the dropped time doesn't come back on the next frame
+        assert_eq!(clock.advance(0.125), 0);
+        assert_eq!(clock.alpha(), 0.5);
+    }
+
+    #[test]
+    fn a_zero_tick_rate_ticks_once_a_second() {
+        assert_eq!(FixedTimestep::new(0).tick_seconds(), 1.0);
+        assert_eq!(clock().tick_seconds(), 0.25);
+    }
+
+    #[test]
+    fn lerp_blends_between_positions() {
+        assert_eq!(lerp([0.0, 4.0], [2.0, 8.0], 0.0), [0.0, 4.0]);
+        assert_eq!(lerp([0.0, 4.0], [2.0, 8.0], 0.25), [0.5, 5.0]);
+        assert_eq!(lerp([0.0, 4.0], [2.0, 8.0], 1.0), [2.0, 8.0]);
+    }
+}
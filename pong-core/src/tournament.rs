@@ -0,0 +1,327 @@
+//! Tournaments between paddle controllers.
+//!
+//! Entrants are numbered in seed order. Each pairing plays a `series` of games, swapping sides
+//! after every game so both entrants play each serve seed from both ends. `record` keeps
+//! everyone's results, and a bracket goes round by round with `next_round` and
+//! `series_winner`. Nothing here starts programs or threads; that is up to the caller.
+
+use std::cmp::Ordering;
+
+use crate::controller::NoInput;
+use crate::{Arena, FixedTimestep, Input, PaddleController, Side};
+
+/// One game to play: the entrants on each side, and the seed its serves are drawn from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Game {
+    pub left: usize,
+    pub right: usize,
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outcome {
+    pub score_left: i32,
+    pub score_right: i32,
+    /// `None` for a draw at the tick limit.
+    pub winner: Option<Side>,
+    pub ticks: u32,
+    /// Ticks each side's bot didn't answer in time, 0 for other controllers.
+    pub late: [u64; 2],
+}
+
+/// The `games` games between `a` and `b`: every other game swaps sides, and every second game
+/// moves on to the next seed from `seed`.
+pub fn series(a: usize, b: usize, games: u32, seed: u64) -> Vec<Game> {
+    (0..games)
+        .map(|game| {
+            let seed = seed.wrapping_add(u64::from(game / 2));
+            match game % 2 {
+                0 => Game { left: a, right: b, seed },
+                _ => Game { left: b, right: a, seed },
+            }
+        })
+        .collect()
+}
+
+/// Plays `game` in a copy of `arena` until someone wins or `max_ticks` have passed. A game
+/// stopped by the tick limit goes to whoever leads, or is a draw.
+pub fn play(arena: &Arena, game: Game, mut controllers: [&mut dyn PaddleController; 2], max_ticks: u32) -> Outcome {
+    let mut arena = arena.clone().with_seed(game.seed);
+    let dt = FixedTimestep::new(arena.physics.tick_rate).tick_seconds();
+
+    let mut ticks = 0;
+    while !arena.state.is_over() && ticks < max_ticks {
+        let mut input = Input::default();
+        for side in [Side::Left, Side::Right] {
+            let context = arena.control_context(side, dt, &NoInput);
+            let controller = &mut controllers[side as usize];
+            let serve = controller.serve(&context);
+            let movement = controller.movement(&context);
+            match side {
+                Side::Left => {
+                    input.left = movement;
+                    input.serve_left = serve;
+                }
+                Side::Right => {
+                    input.right = movement;
+                    input.serve_right = serve;
+                }
+            }
+        }
+        arena.step(input, dt);
+        ticks += 1;
+    }
+
+    let scores = &arena.scores;
+    let winner = arena.state.winner.or(match scores.score_left.cmp(&scores.score_right) {
+        Ordering::Greater => Some(Side::Left),
+        Ordering::Less => Some(Side::Right),
+        Ordering::Equal => None,
+    });
+    Outcome {
+        score_left: scores.score_left,
+        score_right: scores.score_right,
+        winner,
+        ticks,
+        late: [0, 0],
+    }
+}
+
+/// One entrant's results so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Record {
+    pub played: u32,
+    pub won: u32,
+    pub lost: u32,
+    pub drawn: u32,
+    pub points_for: i64,
+    pub points_against: i64,
+    pub late: u64,
+}
+
+impl Record {
+    /// Games won out of those played, with draws as half a win.
+    pub fn win_rate(&self) -> f64 {
+        if self.played == 0 {
+            return 0.0;
+        }
+        (f64::from(self.won) + f64::from(self.drawn) * 0.5) / f64::from(self.played)
+    }
+
+    pub fn difference(&self) -> i64 {
+        self.points_for - self.points_against
+    }
+}
+
+/// Adds each game's outcome to the records of the entrants who played it.
+pub fn record(records: &mut [Record], games: &[Game], outcomes: &[Outcome]) {
+    for (game, outcome) in games.iter().zip(outcomes) {
+        for side in [Side::Left, Side::Right] {
+            let (entrant, own, opponent) = match side {
+                Side::Left => (game.left, outcome.score_left, outcome.score_right),
+                Side::Right => (game.right, outcome.score_right, outcome.score_left),
+            };
+            let record = &mut records[entrant];
+            record.played += 1;
+            match outcome.winner {
+                Some(winner) if winner == side => record.won += 1,
+                Some(_) => record.lost += 1,
+                None => record.drawn += 1,
+            }
+            record.points_for += i64::from(own);
+            record.points_against += i64::from(opponent);
+            record.late += outcome.late[side as usize];
+        }
+    }
+}
+
+/// The entrants from best to worst: by win rate, then by points difference.
+pub fn standings(records: &[Record]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..records.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&records[a], &records[b]);
+        b.win_rate()
+            .partial_cmp(&a.win_rate())
+            .unwrap_or(Ordering::Equal)
+            .then(b.difference().cmp(&a.difference()))
+    });
+    order
+}
+
+/// The winner of a bracket pairing and the games each side won: most games won, then most
+/// points won, then the better seed, `a`.
+pub fn series_winner(a: usize, b: usize, games: &[Game], outcomes: &[Outcome]) -> (usize, [u32; 2]) {
+    let (mut wins, mut points) = ([0; 2], [0i64; 2]);
+    for (game, outcome) in games.iter().zip(outcomes) {
+        for (i, &entrant) in [a, b].iter().enumerate() {
+            let side = if game.left == entrant { Side::Left } else { Side::Right };
+            if outcome.winner == Some(side) {
+                wins[i] += 1;
+            }
+            points[i] += i64::from(match side {
+                Side::Left => outcome.score_left,
+                Side::Right => outcome.score_right,
+            });
+        }
+    }
+    let a_wins = (wins[0], points[0]) >= (wins[1], points[1]);
+    (if a_wins { a } else { b }, wins)
+}
+
+/// Who plays whom in a bracket round.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Round {
+    /// Entrants that go through without playing.
+    pub byes: Vec<usize>,
+    /// The better seed first.
+    pub pairs: Vec<(usize, usize)>,
+}
+
+/// Pairs the entrants still in, given in seed order. The best seeds get byes until a power of
+/// two is left, so only the first round has any; of the rest, the best seed meets the worst,
+/// the second best the second worst and so on.
+pub fn next_round(alive: &[usize]) -> Round {
+    let byes = alive.len().next_power_of_two() - alive.len();
+    let playing = &alive[byes..];
+    Round {
+        byes: alive[..byes].to_vec(),
+        pairs: (0..playing.len() / 2)
+            .map(|i| (playing[i], playing[playing.len() - 1 - i]))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ControlContext;
+
+    struct StandStill;
+
+    impl PaddleController for StandStill {
+        fn movement(&mut self, _context: &ControlContext<'_>) -> f32 {
+            0.0
+        }
+    }
+
+    fn outcome(score_left: i32, score_right: i32, winner: Option<Side>) -> Outcome {
+        Outcome {
+            score_left,
+            score_right,
+            winner,
+            ticks: 100,
+            late: [0, 0],
+        }
+    }
+
+    fn outcome_at(score_left: i32, score_right: i32, winner: Option<Side>, ticks: u32) -> Outcome {
+        Outcome {
+            ticks,
+            ..outcome(score_left, score_right, winner)
+        }
+    }
+
+    #[test]
+    fn series_swaps_sides_and_moves_on_every_second_seed() {
+        let games = series(3, 5, 5, u64::MAX);
+        let expected = [(3, 5, u64::MAX), (5, 3, u64::MAX), (3, 5, 0), (5, 3, 0), (3, 5, 1)];
+        let games: Vec<_> = games.iter().map(|game| (game.left, game.right, game.seed)).collect();
+        assert_eq!(games, expected);
+    }
+
+    #[test]
+    fn three_entrants_give_the_top_seed_a_bye() {
+        let round = next_round(&[0, 1, 2]);
+        assert_eq!(round.byes, vec![0]);
+        assert_eq!(round.pairs, vec![(1, 2)]);
+        // the winner joins the bye for the final
+        let final_round = next_round(&[0, 1]);
+        assert!(final_round.byes.is_empty());
+        assert_eq!(final_round.pairs, vec![(0, 1)]);
+    }
+
+    #[test]
+    fn five_entrants_give_the_top_three_seeds_byes() {
+        let round = next_round(&[0, 1, 2, 3, 4]);
+        assert_eq!(round.byes, vec![0, 1, 2]);
+        assert_eq!(round.pairs, vec![(3, 4)]);
+        let round = next_round(&[0, 1, 2, 4]);
+        assert!(round.byes.is_empty());
+        assert_eq!(round.pairs, vec![(0, 4), (1, 2)]);
+    }
+
+    #[test]
+    fn powers_of_two_have_no_byes() {
+        let round = next_round(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        assert!(round.byes.is_empty());
+        assert_eq!(round.pairs, vec![(0, 7), (1, 6), (2, 5), (3, 4)]);
+        assert_eq!(next_round(&[6]), Round { byes: Vec::new(), pairs: Vec::new() });
+    }
+
+    #[test]
+    fn level_games_at_the_tick_limit_are_draws() {
+        let game = Game { left: 0, right: 1, seed: 4 };
+        let outcome = play(&Arena::default(), game, [&mut StandStill, &mut StandStill], 10);
+        assert_eq!(outcome, outcome_at(0, 0, None, 10));
+
+        // whoever leads when time runs out wins
+        let mut arena = Arena::default();
+        arena.scores.score_right = 2;
+        let outcome = play(&arena, game, [&mut StandStill, &mut StandStill], 10);
+        assert_eq!(outcome, outcome_at(0, 2, Some(Side::Right), 10));
+    }
+
+    #[test]
+    fn games_are_played_out() {
+        let game = Game { left: 0, right: 1, seed: 4 };
+        let mut cpu = crate::AiController::new(crate::Difficulty::Hard, 1);
+        let outcome = play(&Arena::default(), game, [&mut StandStill, &mut cpu], u32::MAX);
+        assert_eq!(outcome.winner, Some(Side::Right));
+        assert!(outcome.score_right > outcome.score_left);
+    }
+
+    #[test]
+    fn records_count_wins_losses_and_draws() {
+        let games = series(0, 1, 3, 0);
+        let outcomes = [
+            outcome(5, 3, Some(Side::Left)),
+            outcome(2, 2, None),
+            Outcome {
+                late: [4, 0],
+                ..outcome(1, 5, Some(Side::Right))
+            },
+        ];
+        let mut records = vec![Record::default(); 2];
+        record(&mut records, &games, &outcomes);
+
+        let expected = Record {
+            played: 3,
+            won: 1,
+            lost: 1,
+            drawn: 1,
+            points_for: 8,
+            points_against: 10,
+            late: 4,
+        };
+        assert_eq!(records[0], expected);
+        assert_eq!((records[1].won, records[1].lost, records[1].drawn), (1, 1, 1));
+        assert_eq!((records[1].difference(), records[1].late), (2, 0));
+        assert_eq!(records[0].win_rate(), 0.5);
+        // level on win rate, so the points decide
+        assert_eq!(standings(&records), vec![1, 0]);
+    }
+
+    #[test]
+    fn series_go_to_wins_then_points_then_the_better_seed() {
+        let games = series(2, 7, 2, 0);
+        // one game each, 7 scored more
+        let split = [outcome(1, 0, Some(Side::Left)), outcome(5, 2, Some(Side::Left))];
+        assert_eq!(series_winner(2, 7, &games, &split), (7, [1, 1]));
+        // one game each and level on points
+        let level = [outcome(5, 1, Some(Side::Left)), outcome(5, 1, Some(Side::Left))];
+        assert_eq!(series_winner(2, 7, &games, &level), (2, [1, 1]));
+        // drawn games count for neither
+        let draws = [outcome(3, 3, None), outcome(0, 1, Some(Side::Right))];
+        assert_eq!(series_winner(2, 7, &games, &draws), (2, [1, 0]));
+    }
+}
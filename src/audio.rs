@@ -4,6 +4,7 @@ use amethyst::{
     ecs::{World, WorldExt},
 };
 use std::{iter::Cycle, vec::IntoIter};
+use pong_core::AudioConfig;
 
 const BOUNCE_SOUND: &str = "audio/bounce.ogg";
 const SCORE_SOUND: &str = "audio/score.ogg";
@@ -30,7 +31,7 @@ pub fn initialize_audio(world: &mut World) {
         let loader = world.read_resource::<Loader>();
 
         let mut sink = world.write_resource::<AudioSink>();
-        sink.set_volume(0.25);
+        sink.set_volume(world.read_resource::<AudioConfig>().music_volume);
 
         let music = MUSIC_TRACKS
             .iter()
@@ -55,18 +56,18 @@ pub fn initialize_audio(world: &mut World) {
     world.insert(music);
 }
 
-pub fn play_bounce_sound(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>) {
+pub fn play_bounce_sound(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>, volume: f32) {
     if let Some(ref output) = output.as_ref() {
         if let Some(sound) = storage.get(&sounds.bounce_sfx) {
-            output.play_once(sound, 1.0);
+            output.play_once(sound, volume);
         }
     }
 }
 
-pub fn play_score_sound(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>) {
+pub fn play_score_sound(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>, volume: f32) {
     if let Some(ref output) = output.as_ref() {
         if let Some(sound) = storage.get(&sounds.score_sfx) {
-            output.play_once(sound, 1.0);
+            output.play_once(sound, volume);
         }
     }
 }
@@ -0,0 +1,317 @@
+//! Draws a match into images without a GPU or a window, for bug reports and highlight reels.
+//! It plays back a replay from `pong --record`, or simulates a match between two computer
+//! players, and writes numbered PNG frames into a directory or, if `--out` ends in `.gif`, one
+//! animated GIF.
+//!
+//! ```bash
+//! cargo run --no-default-features --features "empty" --bin pong-export -- --replay match.replay --out clip.gif
+//! cargo run --no-default-features --features "empty" --bin pong-export -- --seed 7 --points 3 --fps 60 --out frames
+//! ```
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use amethyst::config::Config;
+use amethyst::log::{info, warn};
+use amethyst::utils::application_root_dir;
+use amethyst::window::DisplayConfig;
+use pong_core::controller::NoInput;
+use pong_core::render::{Image, Renderer, SpriteSheet};
+use pong_core::replay::config_hash;
+use pong_core::{
+    AiController, Arena, Difficulty, GameConfig, Input, MatchRules, PaddleController, PhysicsConfig, Replay, Side,
+};
+
+// ticks to export when neither a replay, --ticks nor --points ends the match sooner (one
+// minute at 120 Hz), as with `pong --headless`
+const DEFAULT_MAX_TICKS: u32 = 7200;
+
+struct ExportOptions {
+    out: PathBuf,
+    replay: Option<String>,
+    seed: u64,
+    max_ticks: Option<u32>,
+    max_points: Option<i32>,
+    // tick to start drawing at
+    from: u32,
+    fps: f32,
+    size: Option<(u32, u32)>,
+}
+
+impl ExportOptions {
+    fn from_args<I: Iterator<Item = String>>(mut args: I) -> amethyst::Result<ExportOptions> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut out = None;
+        let mut options = ExportOptions {
+            out: PathBuf::new(),
+            replay: None,
+            seed: now.as_secs() ^ u64::from(now.subsec_nanos()),
+            max_ticks: None,
+            max_points: None,
+            from: 0,
+            fps: 30.0,
+            size: None,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--out" => out = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+                "--replay" => options.replay = Some(next_value(&mut args, &arg)?),
+                "--seed" => options.seed = next_value(&mut args, &arg)?.parse()?,
+                "--ticks" => options.max_ticks = Some(next_value(&mut args, &arg)?.parse()?),
+                "--points" => options.max_points = Some(next_value(&mut args, &arg)?.parse()?),
+                "--from" => options.from = next_value(&mut args, &arg)?.parse()?,
+                "--fps" => options.fps = next_value(&mut args, &arg)?.parse()?,
+                "--size" => options.size = Some(parse_size(&next_value(&mut args, &arg)?)?),
+                other => {
+                    return Err(amethyst::Error::from_string(format!("unknown argument `{}`", other)));
+                }
+            }
+        }
+        options.out = out.ok_or_else(|| amethyst::Error::from_string("expected `--out`".to_string()))?;
+        if options.fps.is_nan() || options.fps <= 0.0 {
+            return Err(amethyst::Error::from_string("`--fps` has to be above 0".to_string()));
+        }
+        Ok(options)
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> amethyst::Result<String> {
+    args.next()
+        .ok_or_else(|| amethyst::Error::from_string(format!("`{}` expects a value", flag)))
+}
+
+// `--size 640x480`
+fn parse_size(value: &str) -> amethyst::Result<(u32, u32)> {
+    let mut parts = value.splitn(2, 'x');
+    match (parts.next().map(str::parse), parts.next().map(str::parse)) {
+        (Some(Ok(width)), Some(Ok(height))) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(amethyst::Error::from_string(format!("`{}` is not a size like 500x500", value))),
+    }
+}
+
+// who moves the paddles: the recorded inputs, or two computer players
+enum Players {
+    Replay(Vec<Input>),
+    Computer([AiController; 2]),
+}
+
+impl Players {
+    // the input for `tick`, or None once a replay has run out
+    fn input(&mut self, arena: &Arena, tick: u32, dt: f32) -> Option<Input> {
+        match self {
+            Players::Replay(inputs) => inputs.get(tick as usize).copied(),
+            Players::Computer(controllers) => {
+                let mut input = Input::default();
+                for side in [Side::Left, Side::Right].iter().copied() {
+                    let context = arena.control_context(side, dt, &NoInput);
+                    let controller = &mut controllers[side as usize];
+                    let (movement, serve) = (controller.movement(&context), controller.serve(&context));
+                    match side {
+                        Side::Left => {
+                            input.left = movement;
+                            input.serve_left = serve;
+                        }
+                        Side::Right => {
+                            input.right = movement;
+                            input.serve_right = serve;
+                        }
+                    }
+                }
+                Some(input)
+            }
+        }
+    }
+}
+
+// where the frames go
+enum FrameWriter {
+    Png { dir: PathBuf, written: u32 },
+    Gif { encoder: gif::Encoder<BufWriter<File>>, delay: u16 },
+}
+
+impl FrameWriter {
+    fn create(options: &ExportOptions, width: u32, height: u32, frame_seconds: f32) -> amethyst::Result<FrameWriter> {
+        let is_gif = options.out.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+        if !is_gif {
+            fs::create_dir_all(&options.out)?;
+            return Ok(FrameWriter::Png {
+                dir: options.out.clone(),
+                written: 0,
+            });
+        }
+        if let Some(parent) = options.out.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let too_large = || amethyst::Error::from_string(format!("a GIF can't be {}x{} pixels", width, height));
+        let width = u16::try_from(width).map_err(|_| too_large())?;
+        let height = u16::try_from(height).map_err(|_| too_large())?;
+        let mut encoder = gif::Encoder::new(BufWriter::new(File::create(&options.out)?), width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        // GIF delays are in hundredths of a second
+        let delay = (frame_seconds * 100.0).round().max(1.0) as u16;
+        Ok(FrameWriter::Gif { encoder, delay })
+    }
+
+    fn write(&mut self, image: &Image) -> amethyst::Result<()> {
+        match self {
+            FrameWriter::Png { dir, written } => {
+                let path = dir.join(format!("frame_{:05}.png", written));
+                let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), image.width, image.height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.write_header()?.write_image_data(&image.pixels)?;
+                *written += 1;
+            }
+            FrameWriter::Gif { encoder, delay } => {
+                let (width, height) = (image.width as u16, image.height as u16);
+                let mut frame = match palette(image) {
+                    Some((palette, indices)) => gif::Frame::from_palette_pixels(width, height, indices, palette, None),
+                    // too many colors for one palette, so they have to be approximated
+                    None => gif::Frame::from_rgba_speed(width, height, &mut image.pixels.clone(), 10),
+                };
+                frame.delay = *delay;
+                encoder.write_frame(&frame)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the image as an RGB palette and an index into it for every pixel, if it has at most 256
+// colors; the frames are drawn with a handful, so this is exact where quantizing would not be
+fn palette(image: &Image) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut colors: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut indices = Vec::with_capacity(image.pixels.len() / 4);
+    let mut last = None;
+    for pixel in image.pixels.chunks_exact(4) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match last {
+            // most pixels are the same as the one before, so skip the lookup for those
+            Some((last_color, index)) if last_color == color => index,
+            _ => match colors.get(&color) {
+                Some(&index) => index,
+                None => {
+                    let index = u8::try_from(colors.len()).ok()?;
+                    colors.insert(color, index);
+                    palette.extend_from_slice(&color);
+                    index
+                }
+            },
+        };
+        indices.push(index);
+        last = Some((color, index));
+    }
+    Some((palette, indices))
+}
+
+// decodes a PNG into 8-bit RGBA
+fn load_texture(path: &Path) -> amethyst::Result<Image> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    let buffer = &buffer[..info.buffer_size()];
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer.to_vec(),
+        png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&p| [p, p, p, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(amethyst::Error::from_string(format!("{} could not be expanded to RGBA", path.display())));
+        }
+    };
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn main() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let app_root = application_root_dir()?;
+    let config_dir = app_root.join("config");
+    let game_config = GameConfig::load(config_dir.join("game.ron"))?;
+    let physics_config = PhysicsConfig::load(config_dir.join("physics.ron"))?;
+    let rules = MatchRules::load(config_dir.join("rules.ron"))?;
+    let display_config = DisplayConfig::load(config_dir.join("display.ron"))?;
+    let options = ExportOptions::from_args(std::env::args().skip(1))?;
+
+    // the same sprites the game draws with, at the size of its window
+    let texture_dir = app_root.join("assets").join("texture");
+    let sheet = SpriteSheet::load(texture_dir.join("pong_spritesheet.ron"))?;
+    let texture = load_texture(&texture_dir.join("pong_spritesheet.png"))?;
+    let (width, height) = options.size.or(display_config.dimensions).unwrap_or((500, 500));
+    let renderer = Renderer::new(&sheet, texture, width, height)?;
+
+    let (seed, mut players, max_ticks) = match options.replay {
+        Some(ref path) => {
+            let file = File::open(path)
+                .map_err(|e| amethyst::Error::from_string(format!("failed to open replay `{}`: {}", path, e)))?;
+            let replay = Replay::read_from(std::io::BufReader::new(file))
+                .map_err(|e| amethyst::Error::from_string(format!("failed to read replay `{}`: {}", path, e)))?;
+            if replay.config_hash != config_hash(&game_config, &physics_config, &rules) {
+                warn!("the replay was recorded with different settings in config/ and may not play back the same");
+            }
+            (replay.seed, Players::Replay(replay.inputs), options.max_ticks)
+        }
+        None => {
+            let controllers = [
+                AiController::new(Difficulty::Hard, options.seed),
+                AiController::new(Difficulty::Hard, options.seed.wrapping_add(1)),
+            ];
+            // a match with no stop condition would never end, so fall back to a tick limit
+            let max_ticks = match (options.max_ticks, options.max_points) {
+                (None, None) => Some(DEFAULT_MAX_TICKS),
+                (max_ticks, _) => max_ticks,
+            };
+            (options.seed, Players::Computer(controllers), max_ticks)
+        }
+    };
+
+    // draw every `step`th tick, as near to `--fps` as the tick rate allows
+    let tick_rate = physics_config.tick_rate as f32;
+    let dt = 1.0 / tick_rate;
+    let step = (tick_rate / options.fps).round().max(1.0) as u32;
+    let mut writer = FrameWriter::create(&options, width, height, step as f32 * dt)?;
+    let mut arena = Arena::new(&game_config, physics_config, rules).with_seed(seed);
+
+    let mut tick = 0;
+    let mut frames = 0;
+    loop {
+        if tick >= options.from && (tick - options.from).is_multiple_of(step) {
+            writer.write(&renderer.render(&arena))?;
+            frames += 1;
+        }
+        let scores = &arena.scores;
+        let points_done = options
+            .max_points
+            .is_some_and(|max| scores.score_left >= max || scores.score_right >= max);
+        if points_done || arena.state.is_over() || max_ticks.is_some_and(|max| tick >= max) {
+            break;
+        }
+        let input = match players.input(&arena, tick, dt) {
+            Some(input) => input,
+            None => break,
+        };
+        arena.step(input, dt);
+        tick += 1;
+    }
+
+    info!(
+        "wrote {} frames of {} ticks to {}, final score: left {} - right {}",
+        frames,
+        tick,
+        options.out.display(),
+        arena.scores.score_left,
+        arena.scores.score_right
+    );
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Serves `pong_core::gym::Env` to reinforcement learning trainers, one JSON object per line,
+//! over stdin and stdout or, with `--socket`, a local Unix socket. Every socket connection gets
+//! its own match, so a trainer can run several in parallel.
+//!
+//! ```bash
+//! cargo run --release --no-default-features --features "empty" --bin pong-gym -- --ticks-per-step 4
+//! cargo run --release --no-default-features --features "empty" --bin pong-gym -- --socket /tmp/pong-gym.sock
+//! ```
+//!
+//! Send `{"cmd": "reset", "seed": 7}` to start a match and `{"cmd": "step", "left": 1.0,
+//! "right": -1.0}` to play a step; see `pong_core::gym` for the replies.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use amethyst::config::Config;
+use amethyst::utils::application_root_dir;
+use pong_core::gym::{Env, EnvSettings, Request, Response};
+use pong_core::{Arena, GameConfig, MatchRules, PhysicsConfig};
+
+struct GymOptions {
+    socket: Option<PathBuf>,
+    settings: EnvSettings,
+}
+
+impl GymOptions {
+    fn from_args<I: Iterator<Item = String>>(mut args: I) -> amethyst::Result<GymOptions> {
+        let mut options = GymOptions {
+            socket: None,
+            settings: EnvSettings::default(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--socket" => options.socket = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+                "--ticks-per-step" => options.settings.ticks_per_step = next_value(&mut args, &arg)?.parse()?,
+                "--max-ticks" => options.settings.max_ticks = Some(next_value(&mut args, &arg)?.parse()?),
+                "--no-auto-serve" => options.settings.auto_serve = false,
+                other => {
+                    return Err(amethyst::Error::from_string(format!("unknown argument `{}`", other)));
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> amethyst::Result<String> {
+    args.next()
+        .ok_or_else(|| amethyst::Error::from_string(format!("`{}` expects a value", flag)))
+}
+
+// answers requests from `input` until it closes
+fn serve<R: BufRead, W: Write>(env: &mut Env, input: R, mut output: W) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => env.handle(request),
+            Err(e) => Response::Error { error: e.to_string() },
+        };
+        serde_json::to_writer(&mut output, &response)?;
+        output.write_all(b"\n")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn listen(path: PathBuf, arena: Arena, settings: EnvSettings) -> amethyst::Result<()> {
+    use std::fs;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    // a socket left behind by an earlier run would keep us from binding; anything else at the
+    // path is left alone
+    if fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    eprintln!("pong-gym listening on {}", path.display());
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let mut env = Env::new(arena.clone(), settings.clone());
+        thread::spawn(move || {
+            let result = stream
+                .try_clone()
+                .and_then(|input| serve(&mut env, BufReader::new(input), stream));
+            if let Err(e) = result {
+                eprintln!("pong-gym connection failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_: PathBuf, _: Arena, _: EnvSettings) -> amethyst::Result<()> {
+    Err(amethyst::Error::from_string("`--socket` needs Unix sockets, use stdin and stdout instead".to_string()))
+}
+
+// stdout carries the protocol, and amethyst's logger writes there, so it is never started;
+// anything for people goes to stderr
+fn main() -> amethyst::Result<()> {
+    let config_dir = application_root_dir()?.join("config");
+    let game_config = GameConfig::load(config_dir.join("game.ron"))?;
+    let physics_config = PhysicsConfig::load(config_dir.join("physics.ron"))?;
+    let rules = MatchRules::load(config_dir.join("rules.ron"))?;
+    let options = GymOptions::from_args(std::env::args().skip(1))?;
+
+    let arena = Arena::new(&game_config, physics_config, rules);
+    match options.socket {
+        Some(path) => listen(path, arena, options.settings),
+        None => {
+            let mut env = Env::new(arena, options.settings);
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            serve(&mut env, stdin.lock(), stdout.lock())?;
+            Ok(())
+        }
+    }
+}
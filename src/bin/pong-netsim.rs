@@ -0,0 +1,290 @@
+//! Plays a match between two computer players over a simulated bad link and reports how the
+//! netcode copes with each phase of a scenario. Everything runs in one process over localhost,
+//! and the link's clock is simulated, so a minute of play takes a few seconds.
+//!
+//! ```bash
+//! cargo run --no-default-features --features "empty" --bin pong-netsim -- scenarios/bad_link.ron
+//! cargo run --no-default-features --features "empty" --bin pong-netsim -- scenarios/bad_link.ron --server
+//! ```
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use amethyst::config::Config;
+use amethyst::utils::application_root_dir;
+use pong_core::controller::NoInput;
+use pong_core::net::{
+    ClientSession, ClientSettings, MatchServer, NetError, Phase, RollbackSession, RollbackSettings, Scenario,
+    ServerSettings, SideInput, SimulatedTransport, UdpTransport,
+};
+use pong_core::replay::config_hash;
+use pong_core::{
+    AiController, Arena, Difficulty, FixedTimestep, GameConfig, MatchRules, PaddleController, PhysicsConfig, Side,
+};
+
+struct SimOptions {
+    scenario: String,
+    // play through pong-server's MatchServer instead of peer to peer
+    server: bool,
+    seed: u64,
+    settings: RollbackSettings,
+}
+
+impl SimOptions {
+    fn from_args<I: Iterator<Item = String>>(mut args: I) -> amethyst::Result<SimOptions> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut scenario = None;
+        let mut options = SimOptions {
+            scenario: String::new(),
+            server: false,
+            seed: now.as_secs() ^ u64::from(now.subsec_nanos()),
+            settings: RollbackSettings::default(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--server" => options.server = true,
+                "--seed" => options.seed = next_value(&mut args, &arg)?.parse()?,
+                "--input-delay" => options.settings.input_delay = next_value(&mut args, &arg)?.parse()?,
+                other if !other.starts_with("--") && scenario.is_none() => scenario = Some(other.to_string()),
+                other => {
+                    return Err(amethyst::Error::from_string(format!("unknown argument `{}`", other)));
+                }
+            }
+        }
+        options.scenario =
+            scenario.ok_or_else(|| amethyst::Error::from_string("expected a scenario file".to_string()))?;
+        Ok(options)
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> amethyst::Result<String> {
+    args.next()
+        .ok_or_else(|| amethyst::Error::from_string(format!("`{}` expects a value", flag)))
+}
+
+// what happened during one phase of the scenario, for the left and right player
+struct PhaseReport {
+    name: String,
+    seconds: f32,
+    ticks: u32,
+    // ticks a player's game stood still waiting for the network
+    stalls: [u32; 2],
+    // rollbacks in a peer-to-peer match, paddle corrections in a server match
+    corrections: [u32; 2],
+    deepest_prediction: u32,
+    sent: u64,
+    dropped: u64,
+}
+
+impl PhaseReport {
+    fn new(phase: &Phase) -> PhaseReport {
+        PhaseReport {
+            name: phase.name.clone(),
+            seconds: phase.seconds,
+            ticks: 0,
+            stalls: [0; 2],
+            corrections: [0; 2],
+            deepest_prediction: 0,
+            sent: 0,
+            dropped: 0,
+        }
+    }
+}
+
+impl fmt::Display for PhaseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} s): {} ticks, stalled {}/{}, corrected {}/{}, predicted up to {} frames ahead, lost {} of {} datagrams",
+            self.name,
+            self.seconds,
+            self.ticks,
+            self.stalls[0],
+            self.stalls[1],
+            self.corrections[0],
+            self.corrections[1],
+            self.deepest_prediction,
+            self.dropped,
+            self.sent,
+        )
+    }
+}
+
+// the two computer players
+struct Players {
+    controllers: [AiController; 2],
+}
+
+impl Players {
+    fn new(seed: u64) -> Players {
+        Players {
+            controllers: [
+                AiController::new(Difficulty::Hard, seed),
+                AiController::new(Difficulty::Hard, seed.wrapping_add(1)),
+            ],
+        }
+    }
+
+    fn input(&mut self, arena: &Arena, side: Side, dt: f32) -> SideInput {
+        let context = arena.control_context(side, dt, &NoInput);
+        let controller = &mut self.controllers[side as usize];
+        SideInput {
+            serve: controller.serve(&context),
+            movement: controller.movement(&context),
+        }
+    }
+}
+
+fn main() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let config_dir = application_root_dir()?.join("config");
+    let game_config = GameConfig::load(config_dir.join("game.ron"))?;
+    let physics_config = PhysicsConfig::load(config_dir.join("physics.ron"))?;
+    let rules = MatchRules::load(config_dir.join("rules.ron"))?;
+    let options = SimOptions::from_args(std::env::args().skip(1))?;
+    let scenario = Scenario::load(&options.scenario)?;
+
+    let hash = config_hash(&game_config, &physics_config, &rules);
+    let dt = FixedTimestep::new(physics_config.tick_rate).tick_seconds();
+    let arena = Arena::new(&game_config, physics_config, rules);
+    let mut reports = Vec::new();
+    let result = if options.server {
+        run_server_match(&scenario, arena, hash, dt, options.seed, &mut reports)
+    } else {
+        run_peer_match(&scenario, arena, hash, dt, &options, &mut reports)
+    };
+
+    for report in &reports {
+        println!("{}", report);
+    }
+    match result {
+        Ok(score) => {
+            println!("{}", score);
+            Ok(())
+        }
+        Err(e) => Err(amethyst::Error::from_string(format!("the match broke down: {}", e))),
+    }
+}
+
+// starts a new report whenever the scenario moves on to its next phase; None once it is over
+fn phase_report<'a>(
+    scenario: &'a Scenario,
+    tick: u32,
+    dt: f32,
+    reports: &'a mut Vec<PhaseReport>,
+) -> Option<(&'a mut PhaseReport, Option<&'a Phase>)> {
+    let (index, phase) = scenario.phase_at(tick as f32 * dt)?;
+    let started = index == reports.len();
+    if started {
+        reports.push(PhaseReport::new(phase));
+    }
+    let report = reports.last_mut()?;
+    Some((report, if started { scenario.phases.get(index) } else { None }))
+}
+
+// the left peer's link is the bad one; it delays and loses datagrams both ways
+fn run_peer_match(
+    scenario: &Scenario,
+    arena: Arena,
+    hash: u64,
+    dt: f32,
+    options: &SimOptions,
+    reports: &mut Vec<PhaseReport>,
+) -> Result<String, NetError> {
+    let (left, right) = UdpTransport::pair()?;
+    let left = SimulatedTransport::new(left, options.seed);
+    let settings = &options.settings;
+    let mut left = RollbackSession::new(left, Side::Left, arena.clone(), options.seed, hash, settings.clone());
+    let mut right = RollbackSession::new(right, Side::Right, arena, options.seed, hash, settings.clone());
+    let mut players = Players::new(options.seed);
+
+    let mut tick = 0;
+    while let Some((report, started)) = phase_report(scenario, tick, dt, reports) {
+        if let Some(phase) = started {
+            left.transport_mut().set_conditions(phase.conditions.clone());
+        }
+        let (sent, dropped) = (left.transport().sent(), left.transport().dropped());
+        let rollbacks = [left.rollbacks(), right.rollbacks()];
+        left.transport_mut().advance(dt)?;
+
+        let input = players.input(left.arena(), Side::Left, dt);
+        report.stalls[0] += left.advance(input, dt)?.is_none() as u32;
+        let input = players.input(right.arena(), Side::Right, dt);
+        report.stalls[1] += right.advance(input, dt)?.is_none() as u32;
+
+        report.ticks += 1;
+        report.corrections[0] += left.rollbacks() - rollbacks[0];
+        report.corrections[1] += right.rollbacks() - rollbacks[1];
+        let deepest = left.prediction_depth().max(right.prediction_depth());
+        report.deepest_prediction = report.deepest_prediction.max(deepest);
+        report.sent += left.transport().sent() - sent;
+        report.dropped += left.transport().dropped() - dropped;
+        tick += 1;
+    }
+
+    Ok(format!(
+        "final score: left {} - right {} (as the left peer saw it), left {} - right {} (as the right peer saw it)",
+        left.arena().scores.score_left,
+        left.arena().scores.score_right,
+        right.arena().scores.score_left,
+        right.arena().scores.score_right,
+    ))
+}
+
+// both clients have a bad link to the server
+fn run_server_match(
+    scenario: &Scenario,
+    arena: Arena,
+    hash: u64,
+    dt: f32,
+    seed: u64,
+    reports: &mut Vec<PhaseReport>,
+) -> Result<String, NetError> {
+    let settings = ServerSettings::default();
+    let mut server = MatchServer::bind("127.0.0.1:0", arena.clone().with_seed(seed), hash, settings)?;
+    let addr = server.local_addr()?;
+    let mut clients = [
+        connect(addr, arena.clone(), hash, seed)?,
+        connect(addr, arena, hash, seed.wrapping_add(1))?,
+    ];
+    let mut players = Players::new(seed);
+
+    let mut tick = 0;
+    while let Some((report, started)) = phase_report(scenario, tick, dt, reports) {
+        for (index, client) in clients.iter_mut().enumerate() {
+            if let Some(phase) = started {
+                client.transport_mut().set_conditions(phase.conditions.clone());
+            }
+            let (sent, dropped, corrections) =
+                (client.transport().sent(), client.transport().dropped(), client.corrections());
+            client.transport_mut().advance(dt)?;
+
+            let side = client.local_side().unwrap_or(Side::Left);
+            let input = players.input(client.arena(), side, dt);
+            report.stalls[index] += client.advance(input, dt)?.is_none() as u32;
+
+            report.corrections[index] += client.corrections() - corrections;
+            report.deepest_prediction = report.deepest_prediction.max(client.prediction_depth());
+            report.sent += client.transport().sent() - sent;
+            report.dropped += client.transport().dropped() - dropped;
+        }
+        server.update(dt)?;
+        report.ticks += 1;
+        tick += 1;
+    }
+
+    let scores = &server.arena().scores;
+    Ok(format!("final score: left {} - right {}", scores.score_left, scores.score_right))
+}
+
+fn connect(
+    addr: SocketAddr,
+    arena: Arena,
+    hash: u64,
+    seed: u64,
+) -> Result<ClientSession<SimulatedTransport<UdpTransport>>, NetError> {
+    let transport = SimulatedTransport::new(UdpTransport::new("127.0.0.1:0", addr)?, seed);
+    Ok(ClientSession::new(transport, arena, hash, ClientSettings::default()))
+}
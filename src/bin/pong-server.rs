@@ -0,0 +1,134 @@
+//! A dedicated server for `pong --connect`. It plays the match without a window, using only
+//! the rules in pong-core, and sends both clients snapshots of it. The clients only send their
+//! paddle inputs, so neither of them can change the score. Up to `--max-spectators` `pong --watch`
+//! spectators can follow the match too, `--spectator-delay` seconds behind.
+//!
+//! ```bash
+//! cargo run --no-default-features --features "empty" --bin pong-server -- --bind 0.0.0.0:7100 --spectator-delay 2
+//! ```
+
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use amethyst::config::Config;
+use amethyst::log::info;
+use amethyst::utils::application_root_dir;
+use pong_core::net::{MatchServer, ServerSettings};
+use pong_core::replay::config_hash;
+use pong_core::{Arena, Event, FixedTimestep, GameConfig, MatchRules, PhysicsConfig, Side};
+
+// how long the final score stays up for the clients after the spectators have seen the end,
+// before the server quits
+const LINGER_SECONDS: f32 = 5.0;
+
+struct ServerOptions {
+    bind: String,
+    seed: u64,
+    settings: ServerSettings,
+}
+
+impl ServerOptions {
+    fn from_args<I: Iterator<Item = String>>(mut args: I) -> amethyst::Result<ServerOptions> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut options = ServerOptions {
+            bind: "0.0.0.0:7100".to_string(),
+            seed: now.as_secs() ^ u64::from(now.subsec_nanos()),
+            settings: ServerSettings::default(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bind" => options.bind = next_value(&mut args, &arg)?,
+                "--seed" => options.seed = next_value(&mut args, &arg)?.parse()?,
+                "--snapshot-interval" => options.settings.snapshot_interval = next_value(&mut args, &arg)?.parse()?,
+                "--timeout" => options.settings.timeout = next_value(&mut args, &arg)?.parse()?,
+                "--spectator-delay" => options.settings.spectator_delay = next_value(&mut args, &arg)?.parse()?,
+                "--max-spectators" => options.settings.max_spectators = next_value(&mut args, &arg)?.parse()?,
+                other => {
+                    return Err(amethyst::Error::from_string(format!("unknown argument `{}`", other)));
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> amethyst::Result<String> {
+    args.next()
+        .ok_or_else(|| amethyst::Error::from_string(format!("`{}` expects a value", flag)))
+}
+
+fn main() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let config_dir = application_root_dir()?.join("config");
+    let game_config = GameConfig::load(config_dir.join("game.ron"))?;
+    let physics_config = PhysicsConfig::load(config_dir.join("physics.ron"))?;
+    let rules = MatchRules::load(config_dir.join("rules.ron"))?;
+    let options = ServerOptions::from_args(std::env::args().skip(1))?;
+
+    let hash = config_hash(&game_config, &physics_config, &rules);
+    let spectator_delay = options.settings.spectator_delay;
+    let arena = Arena::new(&game_config, physics_config.clone(), rules).with_seed(options.seed);
+    let mut server = MatchServer::bind(options.bind.as_str(), arena, hash, options.settings)?;
+    info!("waiting for players on {}", server.local_addr()?);
+
+    let dt = FixedTimestep::new(physics_config.tick_rate).tick_seconds();
+    let tick = Duration::from_secs_f32(dt);
+    let mut next_tick = Instant::now();
+    let mut players = [None, None];
+    let mut spectators = 0;
+    let mut linger = None;
+    loop {
+        for event in server.update(dt)? {
+            let scores = &server.arena().scores;
+            match event {
+                Event::Point(_) => info!("score: left {} - right {}", scores.score_left, scores.score_right),
+                Event::Won(side) => {
+                    info!("{} player wins {} - {}", side_name(side), scores.score_left, scores.score_right);
+                    linger = Some(LINGER_SECONDS + spectator_delay);
+                }
+                _ => {}
+            }
+        }
+
+        for side in [Side::Left, Side::Right].iter().copied() {
+            let player = server.player(side);
+            if player != players[side as usize] {
+                match player {
+                    Some(addr) => info!("{} paddle: {}", side_name(side), addr),
+                    None => info!("{} paddle: player left, waiting for another", side_name(side)),
+                }
+                players[side as usize] = player;
+            }
+        }
+
+        let watching = server.spectators().count();
+        if watching != spectators {
+            info!("{} watching", watching);
+            spectators = watching;
+        }
+
+        if let Some(seconds) = linger.as_mut() {
+            *seconds -= dt;
+            if *seconds <= 0.0 {
+                return Ok(());
+            }
+        }
+
+        // keep to the tick rate, without trying to catch up after a long stall
+        next_tick += tick;
+        let now = Instant::now();
+        if next_tick > now {
+            thread::sleep(next_tick - now);
+        } else {
+            next_tick = now;
+        }
+    }
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Left => "left",
+        Side::Right => "right",
+    }
+}
@@ -0,0 +1,51 @@
+use amethyst::ecs::prelude::{Component, DenseVecStorage};
+use amethyst::log::{error, info};
+use pong_core::bot::{BotController, BotSettings, Fallback};
+use pong_core::controller::{AxisController, GamepadController, PointerController, ScriptedController};
+use pong_core::{AiController, PaddleController, ReplayController, Side};
+
+use crate::options::ControllerChoice;
+
+// Controller holds whatever decides how a paddle moves each tick. PaddleSystem asks it for a
+// movement without caring whether that comes from a person, a script or the computer.
+pub struct Controller(pub Box<dyn PaddleController>);
+
+impl Controller {
+    pub fn new(choice: &ControllerChoice, side: Side) -> Controller {
+        let controller: Box<dyn PaddleController> = match choice {
+            ControllerChoice::Human => Box::new(match side {
+                Side::Left => AxisController::new("left_paddle").with_serve_action("left_serve"),
+                Side::Right => AxisController::new("right_paddle").with_serve_action("right_serve"),
+            }),
+            ControllerChoice::Gamepad(pad) => Box::new(GamepadController::new(*pad)),
+            ControllerChoice::Mouse => Box::new(PointerController),
+            // the ai seed only needs to differ between the two sides
+            ControllerChoice::Cpu(difficulty) => Box::new(AiController::new(*difficulty, side as u64)),
+            ControllerChoice::Script(steps) => Box::new(ScriptedController::new(steps.clone())),
+            ControllerChoice::Replay(inputs) => Box::new(ReplayController::new(inputs.clone(), side)),
+            ControllerChoice::Bot(addr, settings) => bot_controller(addr, side, settings),
+        };
+        Controller(controller)
+    }
+}
+
+// a paddle that a bot can take over, or just its fallback if the port can't be listened on
+fn bot_controller(addr: &str, side: Side, settings: &BotSettings) -> Box<dyn PaddleController> {
+    match BotController::listen(addr, side, settings.clone()) {
+        Ok(controller) => {
+            info!("waiting for a bot on {}", addr);
+            Box::new(controller)
+        }
+        Err(e) => {
+            error!("failed to listen for a bot on {}: {}", addr, e);
+            match settings.fallback {
+                Fallback::Cpu(difficulty) => Box::new(AiController::new(difficulty, side as u64)),
+                Fallback::Stop | Fallback::Repeat => Box::new(ScriptedController::new(Vec::new())),
+            }
+        }
+    }
+}
+
+impl Component for Controller {
+    type Storage = DenseVecStorage<Self>;
+}
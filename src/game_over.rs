@@ -0,0 +1,77 @@
+use amethyst::{
+    ecs::prelude::Entity,
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
+    prelude::*,
+};
+
+use crate::menu::MainMenu;
+use crate::pong::{load_font, Pong, ScoreBoard, Side};
+use crate::ui::{create_text_line, delete_entities};
+
+// GameOver is the results screen, showing who won the match. R starts a rematch with the same
+// players, Escape goes back to the title menu.
+pub struct GameOver {
+    winner: Side,
+    scores: ScoreBoard,
+    // a network match can't be restarted from here, the other side would have to agree first
+    rematch: bool,
+    ui: Vec<Entity>,
+}
+
+impl GameOver {
+    pub fn new(winner: Side, scores: ScoreBoard) -> GameOver {
+        GameOver {
+            winner,
+            scores,
+            rematch: true,
+            ui: Vec::new(),
+        }
+    }
+
+    // the results of a match over the network, which only offers the way back to the menu
+    pub fn network(winner: Side, scores: ScoreBoard) -> GameOver {
+        GameOver {
+            rematch: false,
+            ..GameOver::new(winner, scores)
+        }
+    }
+}
+
+impl SimpleState for GameOver {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        let font = load_font(world);
+
+        let winner = match self.winner {
+            Side::Left => "Left player wins!",
+            Side::Right => "Right player wins!",
+        };
+        let score = format!("{} - {}", self.scores.score_left, self.scores.score_right);
+        let keys = if self.rematch { "R: rematch   Esc: menu" } else { "Esc: menu" };
+
+        self.ui = vec![
+            create_text_line(world, &font, winner, 40., 60.),
+            create_text_line(world, &font, &score, 40., 0.),
+            create_text_line(world, &font, keys, 20., -60.),
+        ];
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn handle_event(&mut self, _data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+            if is_key_down(event, VirtualKeyCode::Escape) {
+                return Trans::Switch(Box::new(MainMenu::default()));
+            }
+            if self.rematch && is_key_down(event, VirtualKeyCode::R) {
+                return Trans::Switch(Box::new(Pong::default()));
+            }
+        }
+        Trans::None
+    }
+}
@@ -0,0 +1,97 @@
+use amethyst::{
+    ecs::prelude::Dispatcher,
+    prelude::*,
+};
+use pong_core::{MatchState, Side};
+
+use crate::pong::{initialize_ball, initialize_paddles, reset_match, ScoreBoard};
+use crate::replay::save_recording;
+use crate::systems::physics_dispatcher;
+
+// ticks to run when neither --ticks nor --points is given (one simulated minute at 120 Hz)
+const DEFAULT_MAX_TICKS: u64 = 7200;
+
+// HeadlessConfig holds the stop conditions parsed from the command line
+#[derive(Default)]
+pub struct HeadlessConfig {
+    pub max_ticks: Option<u64>,
+    pub max_points: Option<i32>,
+}
+
+impl HeadlessConfig {
+    // a match with no stop condition would never end, so fall back to a tick limit
+    pub fn with_default_limit(mut self) -> HeadlessConfig {
+        if self.max_ticks.is_none() && self.max_points.is_none() {
+            self.max_ticks = Some(DEFAULT_MAX_TICKS);
+        }
+        self
+    }
+}
+
+// HeadlessPong plays a match without a window, sprites or sound. Every frame is exactly one
+// physics tick, so the wall clock never matters, and it quits once one of the stop conditions
+// is met.
+pub struct HeadlessPong {
+    config: HeadlessConfig,
+    ticks: u64,
+    physics: Option<Dispatcher<'static, 'static>>,
+}
+
+impl HeadlessPong {
+    pub fn new(config: HeadlessConfig) -> HeadlessPong {
+        HeadlessPong {
+            config,
+            ticks: 0,
+            physics: None,
+        }
+    }
+
+    fn finished(&self, scores: &ScoreBoard, match_state: &MatchState) -> bool {
+        let ticks_done = self.config.max_ticks.is_some_and(|max| self.ticks >= max);
+        let points_done = self
+            .config
+            .max_points
+            .is_some_and(|max| scores.score_left >= max || scores.score_right >= max);
+        ticks_done || points_done || match_state.is_over()
+    }
+}
+
+impl SimpleState for HeadlessPong {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        reset_match(world);
+        self.physics.replace(physics_dispatcher(world));
+
+        initialize_paddles(world, None);
+        initialize_ball(world, None);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        save_recording(data.world);
+        let scores = data.world.read_resource::<ScoreBoard>();
+        println!(
+            "Final score after {} ticks: left {} - right {}",
+            self.ticks, scores.score_left, scores.score_right
+        );
+        match data.world.read_resource::<MatchState>().winner {
+            Some(Side::Left) => println!("Left player wins"),
+            Some(Side::Right) => println!("Right player wins"),
+            None => println!("No winner"),
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        let finished = self.finished(
+            &data.world.read_resource::<ScoreBoard>(),
+            &data.world.read_resource::<MatchState>(),
+        );
+        if finished {
+            return Trans::Quit;
+        }
+        if let Some(physics) = self.physics.as_mut() {
+            physics.dispatch(data.world);
+        }
+        self.ticks += 1;
+        Trans::None
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use amethyst::{
+    core::timing::Time,
+    ecs::prelude::Entity,
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
+    prelude::*,
+};
+use pong_core::timestep::lerp;
+use pong_core::FixedTimestep;
+
+use crate::pong::load_font;
+use crate::ui::{create_text_line, delete_entities};
+
+// seconds of play kept for the instant replay
+pub const INSTANT_REPLAY_SECONDS: f32 = 3.0;
+// the replay runs at this fraction of normal speed
+const SLOW_MOTION: f32 = 0.4;
+
+// Frame is where the ball and both paddles were after one physics tick
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub ball: [f32; 2],
+    pub left: [f32; 2],
+    pub right: [f32; 2],
+}
+
+impl Frame {
+    fn lerp(&self, to: &Frame, alpha: f32) -> Frame {
+        Frame {
+            ball: lerp(self.ball, to.ball, alpha),
+            left: lerp(self.left, to.left, alpha),
+            right: lerp(self.right, to.right, alpha),
+        }
+    }
+}
+
+// History is a ring buffer of the most recent frames. When a point is scored the buffer is
+// copied aside, so the playing state can offer it as an instant replay.
+#[derive(Default)]
+pub struct History {
+    frames: VecDeque<Frame>,
+    last_point: Option<Vec<Frame>>,
+}
+
+impl History {
+    // adds a frame, dropping the oldest ones beyond `capacity`
+    pub fn push(&mut self, frame: Frame, capacity: usize) {
+        self.frames.push_back(frame);
+        while self.frames.len() > capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    // keeps the lead-up to the point just scored; the next point starts a fresh buffer
+    pub fn mark_point(&mut self) {
+        self.last_point = Some(self.frames.drain(..).collect());
+    }
+
+    pub fn take_last_point(&mut self) -> Option<Vec<Frame>> {
+        self.last_point.take()
+    }
+}
+
+// ShownFrame overrides the live positions of the ball and paddles on screen while an instant
+// replay plays, see InterpolationSystem
+#[derive(Default)]
+pub struct ShownFrame(pub Option<Frame>);
+
+// InstantReplay is pushed on top of the match after a point. It plays the last few seconds in
+// slow motion and pops itself when done or when space is pressed. The match underneath is
+// frozen meanwhile, so the serve countdown waits for it.
+pub struct InstantReplay {
+    frames: Vec<Frame>,
+    elapsed: f32,
+    ui: Vec<Entity>,
+}
+
+impl InstantReplay {
+    pub fn new(frames: Vec<Frame>) -> InstantReplay {
+        InstantReplay {
+            frames,
+            elapsed: 0.0,
+            ui: Vec::new(),
+        }
+    }
+}
+
+impl SimpleState for InstantReplay {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        let font = load_font(world);
+
+        self.ui = vec![create_text_line(world, &font, "Replay - Space: skip", 20., -180.)];
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        data.world.insert(ShownFrame(None));
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn handle_event(&mut self, _data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+            if is_key_down(event, VirtualKeyCode::Space) {
+                return Trans::Pop;
+            }
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        self.elapsed += data.world.read_resource::<Time>().delta_seconds() * SLOW_MOTION;
+
+        // the frames are one physics tick apart
+        let tick = self.elapsed / data.world.read_resource::<FixedTimestep>().tick_seconds();
+        let index = tick.floor() as usize;
+        if index + 1 >= self.frames.len() {
+            return Trans::Pop;
+        }
+        let frame = self.frames[index].lerp(&self.frames[index + 1], tick.fract());
+        data.world.insert(ShownFrame(Some(frame)));
+        Trans::None
+    }
+}
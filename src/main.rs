@@ -1,6 +1,6 @@
 use amethyst::{
     prelude::*,
-    core::transform::TransformBundle,
+    core::{frame_limiter::FrameRateLimitStrategy, transform::TransformBundle},
     renderer::{
         plugins::{RenderFlat2D, RenderToWindow},
         types::DefaultBackend,
@@ -11,25 +11,83 @@ use amethyst::{
 };
 use amethyst::audio::{DjSystemDesc, AudioBundle};
 use amethyst::input::{InputBundle, StringBindings};
+use amethyst::config::Config;
+use amethyst::log::warn;
+use amethyst::{LoggerConfig, StdoutLog};
+use pong_core::render::CLEAR_COLOR;
+use pong_core::replay::config_hash;
+use pong_core::{Arena, AudioConfig, FixedTimestep, GameConfig, MatchRules, PhysicsConfig};
 
 mod pong;
 mod systems;
 mod audio;
-use crate::pong::Pong;
+mod headless;
+mod options;
+mod controller;
+mod game_over;
+mod menu;
+mod pause;
+mod ui;
+mod replay;
+mod instant_replay;
+mod netplay;
+mod tui;
+mod tournament;
 use crate::audio::Music;
+use crate::headless::HeadlessPong;
+use crate::menu::MainMenu;
+use crate::netplay::NetPong;
+use crate::options::Options;
+use crate::pong::Pong;
+use crate::replay::Recorder;
+use crate::tui::TuiPong;
 
 fn main() -> amethyst::Result<()> {
-    // enable basic logging to console
-    amethyst::start_logger(Default::default());
+    let mut options = Options::from_args(std::env::args().skip(1))?;
+
+    // enable basic logging to console, except in the terminal frontend, which draws over it
+    let mut logger = LoggerConfig::default();
+    if options.tui {
+        logger.stdout = StdoutLog::Off;
+    }
+    amethyst::start_logger(logger);
 
     // load display.ron to modify window size/title w/o recompiling
     let app_root = application_root_dir()?;
     let display_config_path = app_root.join("config").join("display.ron");
     let binding_path = app_root.join("config").join("bindings.ron");  
+    let game_config = GameConfig::load(app_root.join("config").join("game.ron"))?;
+    let physics_config = PhysicsConfig::load(app_root.join("config").join("physics.ron"))?;
+    let rules = MatchRules::load(app_root.join("config").join("rules.ron"))?;
+    let audio_config = AudioConfig::load(app_root.join("config").join("audio.ron"))?;
+
+    // a tournament plays its games on its own threads, without an application
+    if let Some(config) = options.tournament.take() {
+        let arena = Arena::new(&game_config, physics_config, rules);
+        return tournament::run(&config, &arena);
+    }
 
     let input_bundle = InputBundle::<StringBindings>::new()
         .with_bindings_from_file(binding_path)?;
 
+    if let Some(ref replay) = options.replay {
+        if replay.config_hash != config_hash(&game_config, &physics_config, &rules) {
+            warn!("the replay was recorded with different settings in config/ and may not play back the same");
+        }
+    }
+
+    // `--tui` and `--headless` play a match without a window; build with the `empty` feature
+    // for these. The clock is simulated in a headless run, so there is no reason to wait
+    // between frames, but the terminal frontend runs in real time.
+    if options.tui {
+        let state = TuiPong::default();
+        return run_headless(app_root, input_bundle, game_config, physics_config, rules, options, state, Some(60));
+    }
+    if let Some(config) = options.headless.take() {
+        let state = HeadlessPong::new(config);
+        return run_headless(app_root, input_bundle, game_config, physics_config, rules, options, state, None);
+    }
+
     // basic application setup
     let game_data = GameDataBuilder::default()
         .with_bundle(
@@ -38,7 +96,7 @@ fn main() -> amethyst::Result<()> {
                 // drawing on it.
                 .with_plugin(
                     RenderToWindow::from_config_path(display_config_path)?
-                        .with_clear([0.00196, 0.23726, 0.21765, 1.0]),
+                        .with_clear(CLEAR_COLOR),
                 )
                 // RenderFlat2D plugin is used to render entities witha 'SpriteRender' component.
                 .with_plugin(RenderFlat2D::default())
@@ -54,16 +112,80 @@ fn main() -> amethyst::Result<()> {
             "dj_system",
             &[],
         )
-        .with(systems::PaddleSystem, "paddle_system", &["input_system"])
-        .with(systems::MoveBallsSystem, "ball_system", &[])
-        .with(systems::BounceSystem, "collision_system", &["paddle_system", "ball_system"])
-        .with(systems::WinnerSystem, "winner_system", &["ball_system"]);
+        // the gameplay systems run per physics tick from the Pong state, see `systems::physics_dispatcher`
+        .with(systems::InterpolationSystem, "interpolation_system", &[])
+        // picks up edits to config/*.ron while the game runs
+        .with(systems::HotReloadSystem::new(app_root.join("config")), "hot_reload_system", &[])
+        .with(systems::ConfigErrorSystem::default(), "config_error_system", &["hot_reload_system"]);
 
 
+    // players picked on the command line, and network play, skip the menus and go straight
+    // into a match
     let assets_dir = app_root.join("assets");
-    let mut game = Application::new(assets_dir, Pong::default(), game_data)?;
+    let menu = if let Some(net) = options.net.take() {
+        MainMenu::straight_to(NetPong::new(net))
+    } else if let Some(client) = options.connect.take() {
+        MainMenu::straight_to(NetPong::client(client))
+    } else if let Some(watch) = options.watch.take() {
+        MainMenu::straight_to(NetPong::spectate(watch))
+    } else if options.players_chosen {
+        MainMenu::straight_to(Pong::default())
+    } else {
+        MainMenu::default()
+    };
+    let mut builder = Application::build(assets_dir, menu)?
+        .with_resource(FixedTimestep::new(physics_config.tick_rate))
+        .with_resource(game_config)
+        .with_resource(physics_config)
+        .with_resource(rules)
+        .with_resource(audio_config)
+        .with_resource(options.players)
+        .with_resource(options.seed);
+    if let Some(path) = options.record {
+        builder = builder.with_resource(Recorder::new(path));
+    }
+    let mut game = builder.build(game_data)?;
 
     game.run();
     
     Ok(())
 }
+
+#[allow(clippy::too_many_arguments)]
+fn run_headless<S: SimpleState + 'static>(
+    app_root: std::path::PathBuf,
+    input_bundle: InputBundle<StringBindings>,
+    game_config: GameConfig,
+    physics_config: PhysicsConfig,
+    rules: MatchRules,
+    options: Options,
+    state: S,
+    frame_rate: Option<u32>,
+) -> amethyst::Result<()> {
+    // no rendering, ui or audio; the state runs the gameplay systems itself
+    let game_data = GameDataBuilder::default()
+        .with_bundle(TransformBundle::new())?
+        .with_bundle(input_bundle)?;
+
+    let (strategy, fps) = match frame_rate {
+        Some(fps) => (FrameRateLimitStrategy::Sleep, fps),
+        None => (FrameRateLimitStrategy::Unlimited, 0),
+    };
+    let assets_dir = app_root.join("assets");
+    let mut builder = Application::build(assets_dir, state)?
+        .with_frame_limit(strategy, fps)
+        .with_resource(FixedTimestep::new(physics_config.tick_rate))
+        .with_resource(game_config)
+        .with_resource(physics_config)
+        .with_resource(rules)
+        .with_resource(options.players)
+        .with_resource(options.seed);
+    if let Some(path) = options.record {
+        builder = builder.with_resource(Recorder::new(path));
+    }
+    let mut game = builder.build(game_data)?;
+
+    game.run();
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use amethyst::{
+    ecs::prelude::Entity,
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
+    prelude::*,
+};
+use pong_core::Difficulty;
+
+use crate::options::{ControllerChoice, Players};
+use crate::pong::{load_font, Pong};
+use crate::ui::{create_text_line, delete_entities, set_text};
+
+const DIFFICULTIES: [Difficulty; 4] = [
+    Difficulty::Easy,
+    Difficulty::Medium,
+    Difficulty::Hard,
+    Difficulty::Perfect,
+];
+
+// MainMenu is the title screen. Enter moves on to picking a mode, Escape quits.
+#[derive(Default)]
+pub struct MainMenu {
+    ui: Vec<Entity>,
+    skip_to: Option<Box<dyn State<GameData<'static, 'static>, StateEvent>>>,
+}
+
+impl MainMenu {
+    // goes to `state` straight away, without showing the menus; for matches set up on the
+    // command line
+    pub fn straight_to<S: SimpleState + 'static>(state: S) -> MainMenu {
+        MainMenu {
+            ui: Vec::new(),
+            skip_to: Some(Box::new(state)),
+        }
+    }
+}
+
+impl SimpleState for MainMenu {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if self.skip_to.is_some() {
+            return;
+        }
+        let world = data.world;
+        let font = load_font(world);
+
+        self.ui = vec![
+            create_text_line(world, &font, "PONG", 80., 60.),
+            create_text_line(world, &font, "Enter: play   Esc: quit", 20., -40.),
+        ];
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn update(&mut self, _data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if let Some(state) = self.skip_to.take() {
+            return Trans::Switch(state);
+        }
+        Trans::None
+    }
+
+    fn handle_event(&mut self, _data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) || is_key_down(event, VirtualKeyCode::Escape) {
+                return Trans::Quit;
+            }
+            if is_key_down(event, VirtualKeyCode::Return) {
+                return Trans::Switch(Box::new(ModeSelect::default()));
+            }
+        }
+        Trans::None
+    }
+}
+
+// ModeSelect picks who plays each side, then starts the match. Left and Right change the
+// difficulty of any computer players.
+#[derive(Default)]
+pub struct ModeSelect {
+    ui: Vec<Entity>,
+    difficulty_text: Option<Entity>,
+    difficulty: usize,
+}
+
+impl ModeSelect {
+    fn difficulty(&self) -> Difficulty {
+        DIFFICULTIES[self.difficulty]
+    }
+
+    fn start(&self, world: &mut World, left: ControllerChoice, right: ControllerChoice) -> SimpleTrans {
+        world.insert(Players { left, right });
+        Trans::Switch(Box::new(Pong::default()))
+    }
+}
+
+impl SimpleState for ModeSelect {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        let font = load_font(world);
+        self.difficulty = 1; // medium
+
+        let difficulty_text = create_text_line(world, &font, "", 20., -80.);
+        self.difficulty_text = Some(difficulty_text);
+        set_text(world, difficulty_text, format!("< CPU: {} >", self.difficulty()));
+
+        self.ui = vec![
+            create_text_line(world, &font, "Select mode", 40., 100.),
+            create_text_line(world, &font, "1: Player vs Player", 20., 40.),
+            create_text_line(world, &font, "2: Player vs CPU", 20., 10.),
+            create_text_line(world, &font, "3: CPU vs CPU", 20., -20.),
+            difficulty_text,
+            create_text_line(world, &font, "Esc: back", 20., -140.),
+        ];
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+        self.difficulty_text = None;
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        let world = data.world;
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+            if is_key_down(event, VirtualKeyCode::Escape) {
+                return Trans::Switch(Box::new(MainMenu::default()));
+            }
+
+            let cpu = ControllerChoice::Cpu(self.difficulty());
+            if is_key_down(event, VirtualKeyCode::Key1) {
+                return self.start(world, ControllerChoice::Human, ControllerChoice::Human);
+            }
+            if is_key_down(event, VirtualKeyCode::Key2) {
+                return self.start(world, ControllerChoice::Human, cpu);
+            }
+            if is_key_down(event, VirtualKeyCode::Key3) {
+                return self.start(world, cpu.clone(), cpu);
+            }
+
+            let step = if is_key_down(event, VirtualKeyCode::Left) {
+                DIFFICULTIES.len() - 1
+            } else if is_key_down(event, VirtualKeyCode::Right) {
+                1
+            } else {
+                0
+            };
+            if step != 0 {
+                self.difficulty = (self.difficulty + step) % DIFFICULTIES.len();
+                if let Some(entity) = self.difficulty_text {
+                    set_text(world, entity, format!("< CPU: {} >", self.difficulty()));
+                }
+            }
+        }
+        Trans::None
+    }
+}
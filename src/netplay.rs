@@ -0,0 +1,376 @@
+use amethyst::{
+    assets::{AssetStorage, Handle},
+    audio::{output::Output, Source},
+    core::timing::Time,
+    ecs::prelude::{Entity, Join},
+    input::{is_close_requested, InputHandler, StringBindings},
+    log::error,
+    prelude::*,
+    renderer::SpriteSheet,
+    ui::UiText,
+    window::ScreenDimensions,
+};
+use pong_core::net::{
+    ClientSession, ClientSettings, NetError, RollbackSession, RollbackSettings, SideInput, UdpTransport,
+};
+use pong_core::{
+    Arena, AudioConfig, Event, FixedTimestep, GameConfig, MatchRules, PhysicsConfig, Side,
+};
+
+use crate::audio::{initialize_audio, play_bounce_sound, play_score_sound, Sounds};
+use crate::controller::Controller;
+use crate::game_over::GameOver;
+use crate::options::{MatchSeed, Players};
+use crate::pong::{
+    initialize_ball, initialize_camera, initialize_paddles, initialize_scoreboard, load_font,
+    load_sprite_sheet, reset_match, Ball, Paddle, ScoreBoard, ScoreText, ServeText,
+};
+use crate::replay::world_config_hash;
+use crate::systems::{serve_label, Devices};
+use crate::ui::{create_text_line, set_text};
+
+// NetMatch is inserted as a resource while a network match runs. Both sides started it with
+// the same settings, so they can't change until it is over.
+pub struct NetMatch;
+
+// NetplayConfig is how to reach the other player, parsed from --net, --bind, --peer and
+// --input-delay
+pub struct NetplayConfig {
+    pub side: Side,
+    pub bind: String,
+    pub peer: String,
+    pub settings: RollbackSettings,
+}
+
+impl NetplayConfig {
+    // without --bind / --peer the left side listens on port 7000 and the right side on 7001,
+    // which is all two processes on one machine need
+    pub fn new(side: Side) -> NetplayConfig {
+        let (bind, peer) = match side {
+            Side::Left => ("0.0.0.0:7000", "127.0.0.1:7001"),
+            Side::Right => ("0.0.0.0:7001", "127.0.0.1:7000"),
+        };
+        NetplayConfig {
+            side,
+            bind: bind.to_string(),
+            peer: peer.to_string(),
+            settings: RollbackSettings::default(),
+        }
+    }
+}
+
+// ClientConfig is where pong-server runs, parsed from --connect or --watch and --bind
+pub struct ClientConfig {
+    pub bind: String,
+    pub server: String,
+    pub settings: ClientSettings,
+}
+
+impl ClientConfig {
+    pub fn new(server: String) -> ClientConfig {
+        ClientConfig {
+            bind: "0.0.0.0:0".to_string(),
+            server,
+            settings: ClientSettings::default(),
+        }
+    }
+}
+
+enum Connection {
+    Peer(NetplayConfig),
+    Server(ClientConfig),
+    Spectator(ClientConfig),
+}
+
+// what NetPong needs from a peer-to-peer or a server session
+trait NetSession {
+    fn advance(&mut self, local: SideInput, dt: f32) -> Result<Option<Vec<Event>>, NetError>;
+    fn arena(&self) -> &Arena;
+    // the part of the match that can't change any more, which decides when it is over
+    fn confirmed_arena(&self) -> &Arena;
+    // None until a server has said which paddle is ours
+    fn local_side(&self) -> Option<Side>;
+    fn is_connected(&self) -> bool;
+}
+
+impl NetSession for RollbackSession<UdpTransport> {
+    fn advance(&mut self, local: SideInput, dt: f32) -> Result<Option<Vec<Event>>, NetError> {
+        RollbackSession::advance(self, local, dt)
+    }
+
+    fn arena(&self) -> &Arena {
+        RollbackSession::arena(self)
+    }
+
+    fn confirmed_arena(&self) -> &Arena {
+        RollbackSession::confirmed_arena(self)
+    }
+
+    fn local_side(&self) -> Option<Side> {
+        Some(RollbackSession::local_side(self))
+    }
+
+    fn is_connected(&self) -> bool {
+        RollbackSession::is_connected(self)
+    }
+}
+
+impl NetSession for ClientSession<UdpTransport> {
+    fn advance(&mut self, local: SideInput, dt: f32) -> Result<Option<Vec<Event>>, NetError> {
+        ClientSession::advance(self, local, dt)
+    }
+
+    fn arena(&self) -> &Arena {
+        ClientSession::arena(self)
+    }
+
+    // the score and winner come straight from the server, which doesn't take anything back
+    fn confirmed_arena(&self) -> &Arena {
+        ClientSession::arena(self)
+    }
+
+    fn local_side(&self) -> Option<Side> {
+        ClientSession::local_side(self)
+    }
+
+    fn is_connected(&self) -> bool {
+        ClientSession::is_connected(self)
+    }
+}
+
+// NetPong plays one side of a match against another process over UDP, either directly with
+// the other player or through pong-server. The match runs in a network session rather than the
+// gameplay systems: a peer-to-peer session has to be able to go back and simulate frames again,
+// and a server session only draws what the server sends. Every frame the session's arena is
+// copied into the ball and paddle components for drawing.
+pub struct NetPong {
+    connection: Connection,
+    session: Option<Box<dyn NetSession>>,
+    controller: Option<Controller>,
+    status: Option<Entity>,
+    failed: bool,
+}
+
+impl NetPong {
+    pub fn new(config: NetplayConfig) -> NetPong {
+        NetPong::with_connection(Connection::Peer(config))
+    }
+
+    pub fn client(config: ClientConfig) -> NetPong {
+        NetPong::with_connection(Connection::Server(config))
+    }
+
+    // watches a server match without playing in it
+    pub fn spectate(config: ClientConfig) -> NetPong {
+        NetPong::with_connection(Connection::Spectator(config))
+    }
+
+    fn with_connection(connection: Connection) -> NetPong {
+        NetPong {
+            connection,
+            session: None,
+            controller: None,
+            status: None,
+            failed: false,
+        }
+    }
+
+    // runs one physics tick with the local player's input; false when the session has failed
+    fn tick(&mut self, world: &mut World, dt: f32) -> bool {
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => return false,
+        };
+
+        // a server client only learns its side once the server answers
+        let side = session.local_side();
+        if let (None, Some(side)) = (self.controller.as_ref(), side) {
+            self.controller = Some(Controller::new(world.read_resource::<Players>().for_side(side), side));
+        }
+        let local = match (self.controller.as_mut(), side) {
+            (Some(controller), Some(side)) => {
+                let input = world.read_resource::<InputHandler<StringBindings>>();
+                let screen = world.try_fetch::<ScreenDimensions>();
+                let arena = session.arena();
+                let devices = Devices::new(&input, screen.as_deref(), arena.height);
+                let context = arena.control_context(side, dt, &devices);
+                SideInput {
+                    serve: controller.0.serve(&context),
+                    movement: controller.0.movement(&context),
+                }
+            }
+            _ => SideInput::default(),
+        };
+
+        match session.advance(local, dt) {
+            Ok(Some(events)) => {
+                play_sounds(world, &events);
+                true
+            }
+            Ok(None) => true,
+            Err(e) => {
+                error!("network play stopped: {}", e);
+                false
+            }
+        }
+    }
+
+    // copies the session's arena into the world for drawing and the results screen
+    fn show(&mut self, world: &mut World) {
+        let session = match self.session.as_ref() {
+            Some(session) => session,
+            None => return,
+        };
+        let arena = session.arena();
+
+        for paddle in (&mut world.write_storage::<Paddle>()).join() {
+            paddle.0 = arena.paddle(paddle.side).clone();
+        }
+        for ball in (&mut world.write_storage::<Ball>()).join() {
+            ball.0 = arena.ball.clone();
+        }
+        *world.write_resource::<ScoreBoard>() = arena.scores.clone();
+
+        {
+            let score_text = world.read_resource::<ScoreText>();
+            let serve_text = world.read_resource::<ServeText>();
+            let mut ui_text = world.write_storage::<UiText>();
+            if let Some(text) = ui_text.get_mut(score_text.p1_score) {
+                text.text = arena.scores.score_left.to_string();
+            }
+            if let Some(text) = ui_text.get_mut(score_text.p2_score) {
+                text.text = arena.scores.score_right.to_string();
+            }
+            if let Some(text) = ui_text.get_mut(serve_text.text) {
+                text.text = serve_label(&arena.serve);
+            }
+        }
+
+        if let Some(status) = self.status {
+            let text = match (session.is_connected(), &self.connection) {
+                (true, _) => "",
+                (false, Connection::Spectator(_)) => "Waiting for the match to start",
+                (false, _) => "Waiting for the other player",
+            };
+            set_text(world, status, text.to_string());
+        }
+    }
+}
+
+impl SimpleState for NetPong {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        world.register::<Controller>();
+
+        let sprite_sheet_handle: Handle<SpriteSheet> = load_sprite_sheet(world);
+        reset_match(world);
+        initialize_paddles(world, Some(sprite_sheet_handle.clone()));
+        initialize_ball(world, Some(sprite_sheet_handle));
+        initialize_camera(world);
+        initialize_scoreboard(world);
+        initialize_audio(world);
+
+        let font = load_font(world);
+        self.status = Some(create_text_line(world, &font, "", 20., 0.));
+        world.insert(NetMatch);
+
+        let arena = Arena::new(
+            &world.read_resource::<GameConfig>(),
+            world.read_resource::<PhysicsConfig>().clone(),
+            world.read_resource::<MatchRules>().clone(),
+        );
+        let seed = world.read_resource::<MatchSeed>().0;
+        let config_hash = world_config_hash(world);
+        let (bind, peer) = match &self.connection {
+            Connection::Peer(config) => (&config.bind, &config.peer),
+            Connection::Server(config) | Connection::Spectator(config) => (&config.bind, &config.server),
+        };
+        let transport = match UdpTransport::new(bind.as_str(), peer.as_str()) {
+            Ok(transport) => transport,
+            Err(e) => {
+                error!("failed to open {} for {}: {}", bind, peer, e);
+                self.failed = true;
+                return;
+            }
+        };
+        let session: Box<dyn NetSession> = match &self.connection {
+            Connection::Peer(config) => Box::new(RollbackSession::new(
+                transport,
+                config.side,
+                arena,
+                seed,
+                config_hash,
+                config.settings.clone(),
+            )),
+            Connection::Server(config) => {
+                Box::new(ClientSession::new(transport, arena, config_hash, config.settings.clone()))
+            }
+            Connection::Spectator(config) => {
+                Box::new(ClientSession::spectate(transport, arena, config_hash, config.settings.clone()))
+            }
+        };
+        self.session = Some(session);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.session = None;
+        data.world.remove::<NetMatch>();
+        data.world.delete_all();
+    }
+
+    fn handle_event(&mut self, _data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if self.failed {
+            return Trans::Quit;
+        }
+
+        let (ticks, dt) = {
+            let time = data.world.fetch::<Time>();
+            let mut clock = data.world.write_resource::<FixedTimestep>();
+            (clock.advance(time.delta_seconds()), clock.tick_seconds())
+        };
+        for _ in 0..ticks {
+            if !self.tick(data.world, dt) {
+                self.failed = true;
+                return Trans::Quit;
+            }
+        }
+        self.show(data.world);
+
+        // a point in the predicted frames may still be rolled back, so only a result both sides
+        // have confirmed ends the match
+        let result = self.session.as_ref().and_then(|session| {
+            let arena = session.confirmed_arena();
+            arena.state.winner.map(|winner| (winner, arena.scores.clone()))
+        });
+        if let Some((winner, scores)) = result {
+            return Trans::Switch(Box::new(GameOver::network(winner, scores)));
+        }
+        Trans::None
+    }
+}
+
+fn play_sounds(world: &World, events: &[Event]) {
+    let sounds = match world.try_fetch::<Sounds>() {
+        Some(sounds) => sounds,
+        None => return,
+    };
+    let storage = world.read_resource::<AssetStorage<Source>>();
+    let output = world.try_fetch::<Output>();
+    let volume = world.read_resource::<AudioConfig>().effects_volume;
+    for event in events {
+        match event {
+            Event::Bounce => play_bounce_sound(&sounds, &storage, output.as_deref(), volume),
+            Event::Point(_) => play_score_sound(&sounds, &storage, output.as_deref(), volume),
+            _ => {}
+        }
+    }
+}
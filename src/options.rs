@@ -0,0 +1,303 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use amethyst::config::Config;
+use pong_core::bot::{parse_seconds, BotSettings};
+use pong_core::controller::ScriptStep;
+use pong_core::{Difficulty, Input, Replay, Side};
+
+use crate::headless::HeadlessConfig;
+use crate::netplay::{ClientConfig, NetplayConfig};
+use crate::replay::load_replay;
+use crate::tournament::TournamentConfig;
+
+// ControllerChoice says who moves a paddle, picked with --left / --right
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControllerChoice {
+    Human,
+    Gamepad(u32),
+    Mouse,
+    Cpu(Difficulty),
+    Script(Vec<ScriptStep>),
+    // plays back the inputs of a recorded match, see --replay
+    Replay(Vec<Input>),
+    // a program connected to this address plays, see pong_core::bot
+    Bot(String, BotSettings),
+}
+
+impl FromStr for ControllerChoice {
+    type Err = String;
+
+    // "human", "gamepad[:<pad>]", "mouse", "cpu[:<difficulty>]", "script:<file>" or
+    // "bot:<port>". Scripts are read here so a bad file is reported before the game starts.
+    fn from_str(s: &str) -> Result<ControllerChoice, String> {
+        let (kind, arg) = match s.find(':') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        match (kind, arg) {
+            ("human", None) => Ok(ControllerChoice::Human),
+            ("gamepad", None) => Ok(ControllerChoice::Gamepad(0)),
+            ("gamepad", Some(pad)) => pad
+                .parse()
+                .map(ControllerChoice::Gamepad)
+                .map_err(|_| format!("`{}` is not a gamepad number", pad)),
+            ("mouse", None) => Ok(ControllerChoice::Mouse),
+            ("cpu", None) => Ok(ControllerChoice::Cpu(Difficulty::Medium)),
+            ("cpu", Some(difficulty)) => Ok(ControllerChoice::Cpu(difficulty.parse()?)),
+            ("script", Some(path)) => Vec::<ScriptStep>::load(path)
+                .map(ControllerChoice::Script)
+                .map_err(|e| format!("failed to load script `{}`: {}", path, e)),
+            // a bare port is on localhost, so only local programs can connect
+            ("bot", Some(port)) if port.parse::<u16>().is_ok() => {
+                Ok(ControllerChoice::Bot(format!("127.0.0.1:{}", port), BotSettings::default()))
+            }
+            ("bot", Some(addr)) => Ok(ControllerChoice::Bot(addr.to_string(), BotSettings::default())),
+            _ => Err(format!(
+                "unknown controller `{}`, expected human, gamepad[:pad], mouse, cpu[:difficulty], \
+                 script:file or bot:port",
+                s
+            )),
+        }
+    }
+}
+
+// Players is inserted as a resource so the states know which controller to give each paddle
+#[derive(Clone, Debug)]
+pub struct Players {
+    pub left: ControllerChoice,
+    pub right: ControllerChoice,
+}
+
+impl Default for Players {
+    fn default() -> Players {
+        Players {
+            left: ControllerChoice::Human,
+            right: ControllerChoice::Human,
+        }
+    }
+}
+
+impl Players {
+    pub fn for_side(&self, side: Side) -> &ControllerChoice {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+}
+
+// MatchSeed is inserted as a resource; the serves are drawn from it, so the same seed and the
+// same inputs play the same match
+#[derive(Clone, Copy, Debug)]
+pub struct MatchSeed(pub u64);
+
+impl MatchSeed {
+    // a different seed every run unless --seed is given
+    pub fn from_clock() -> MatchSeed {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        MatchSeed(now.as_secs() ^ u64::from(now.subsec_nanos()))
+    }
+}
+
+// Options holds everything parsed from the command line
+pub struct Options {
+    pub headless: Option<HeadlessConfig>,
+    // --tui: play in the terminal instead of a window
+    pub tui: bool,
+    pub players: Players,
+    // true when --left or --right was given
+    pub players_chosen: bool,
+    pub seed: MatchSeed,
+    // where --record writes the replay of each match
+    pub record: Option<PathBuf>,
+    // the match loaded with --replay
+    pub replay: Option<Replay>,
+    // set by --net: play one side against another process
+    pub net: Option<NetplayConfig>,
+    // set by --connect: play in a match run by pong-server
+    pub connect: Option<ClientConfig>,
+    // set by --watch: watch a match run by pong-server
+    pub watch: Option<ClientConfig>,
+    // set by `pong tournament ...`, which plays no match of its own
+    pub tournament: Option<TournamentConfig>,
+}
+
+impl Options {
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> amethyst::Result<Options> {
+        // the tournament subcommand takes every argument after it, so the loop below sees none
+        let mut args = args.peekable();
+        let tournament = match args.peek().map(String::as_str) {
+            Some("tournament") => {
+                args.next();
+                Some(parse_tournament(&mut args)?)
+            }
+            _ => None,
+        };
+
+        let mut headless = false;
+        let mut tui = false;
+        let mut headless_config = HeadlessConfig::default();
+        let mut players = Players::default();
+        // --ticks or --points, which only a headless run has any use for
+        let mut limit_flag = None;
+        let mut players_chosen = false;
+        let mut seed = MatchSeed::from_clock();
+        let mut record = None;
+        let mut replay = None;
+        let mut net_side = None;
+        let mut bind = None;
+        let mut peer = None;
+        let mut input_delay = None;
+        let mut server = None;
+        let mut watched = None;
+        let mut bot_settings = BotSettings::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => headless = true,
+                "--tui" => tui = true,
+                "--ticks" => {
+                    headless_config.max_ticks = Some(next_value(&mut args, &arg)?.parse()?);
+                    limit_flag = Some(arg);
+                }
+                "--points" => {
+                    headless_config.max_points = Some(next_value(&mut args, &arg)?.parse()?);
+                    limit_flag = Some(arg);
+                }
+                "--seed" => seed = MatchSeed(next_value(&mut args, &arg)?.parse()?),
+                "--record" => record = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+                "--replay" => replay = Some(load_replay(&next_value(&mut args, &arg)?)?),
+                "--net" => net_side = Some(parse_side(&next_value(&mut args, &arg)?)?),
+                "--bind" => bind = Some(next_value(&mut args, &arg)?),
+                "--peer" => peer = Some(next_value(&mut args, &arg)?),
+                "--input-delay" => input_delay = Some(next_value(&mut args, &arg)?.parse()?),
+                "--connect" => server = Some(next_value(&mut args, &arg)?),
+                "--watch" => watched = Some(next_value(&mut args, &arg)?),
+                "--bot-timeout" => bot_settings.timeout = seconds(&next_value(&mut args, &arg)?)?,
+                "--bot-wait" => bot_settings.connect_timeout = seconds(&next_value(&mut args, &arg)?)?,
+                "--bot-fallback" => {
+                    let fallback = next_value(&mut args, &arg)?;
+                    bot_settings.fallback = fallback.parse().map_err(amethyst::Error::from_string)?;
+                }
+                "--left" => {
+                    players.left = parse_controller(&next_value(&mut args, &arg)?)?;
+                    players_chosen = true;
+                }
+                "--right" => {
+                    players.right = parse_controller(&next_value(&mut args, &arg)?)?;
+                    players_chosen = true;
+                }
+                other => {
+                    return Err(amethyst::Error::from_string(format!("unknown argument `{}`", other)));
+                }
+            }
+        }
+
+        if let (Some(flag), false) = (limit_flag, headless) {
+            return Err(amethyst::Error::from_string(format!("`{}` only works with `--headless`", flag)));
+        }
+
+        // the --bot-* flags apply to both sides, wherever they were given
+        for choice in [&mut players.left, &mut players.right] {
+            if let ControllerChoice::Bot(_, settings) = choice {
+                *settings = bot_settings.clone();
+            }
+        }
+
+        // a replay decides the seed and both players, and by default runs to its last tick
+        if let Some(ref replay) = replay {
+            seed = MatchSeed(replay.seed);
+            players = Players {
+                left: ControllerChoice::Replay(replay.inputs.clone()),
+                right: ControllerChoice::Replay(replay.inputs.clone()),
+            };
+            players_chosen = true;
+            if headless_config.max_ticks.is_none() && headless_config.max_points.is_none() {
+                headless_config.max_ticks = Some(replay.inputs.len() as u64);
+            }
+        }
+
+        let client_config = |server| {
+            let mut config = ClientConfig::new(server);
+            config.bind = bind.clone().unwrap_or(config.bind);
+            config
+        };
+        let connect = server.map(client_config);
+        let watch = watched.map(client_config);
+        let net = net_side.map(|side| {
+            let mut config = NetplayConfig::new(side);
+            config.bind = bind.unwrap_or(config.bind);
+            config.peer = peer.unwrap_or(config.peer);
+            config.settings.input_delay = input_delay.unwrap_or(config.settings.input_delay);
+            config
+        });
+
+        Ok(Options {
+            headless: if headless { Some(headless_config.with_default_limit()) } else { None },
+            tui,
+            players,
+            players_chosen,
+            seed,
+            record,
+            replay,
+            net,
+            connect,
+            watch,
+            tournament,
+        })
+    }
+}
+
+// `tournament [flags] <entrant>...`, where an entrant is cpu[:difficulty] or exec:<command>
+fn parse_tournament<I: Iterator<Item = String>>(args: &mut I) -> amethyst::Result<TournamentConfig> {
+    let mut config = TournamentConfig::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => config.format = next_value(args, &arg)?.parse().map_err(amethyst::Error::from_string)?,
+            "--games" => config.games = next_value(args, &arg)?.parse()?,
+            "--threads" => config.threads = next_value(args, &arg)?.parse()?,
+            "--seed" => config.seed = MatchSeed(next_value(args, &arg)?.parse()?),
+            "--max-ticks" => config.max_ticks = next_value(args, &arg)?.parse()?,
+            "--bot-timeout" => config.bot.timeout = seconds(&next_value(args, &arg)?)?,
+            "--bot-wait" => config.bot.connect_timeout = seconds(&next_value(args, &arg)?)?,
+            "--bot-fallback" => {
+                let fallback = next_value(args, &arg)?;
+                config.bot.fallback = fallback.parse().map_err(amethyst::Error::from_string)?;
+            }
+            other if other.starts_with("--") => {
+                return Err(amethyst::Error::from_string(format!("unknown argument `{}`", other)));
+            }
+            entrant => config.entrants.push(entrant.parse().map_err(amethyst::Error::from_string)?),
+        }
+    }
+    if config.entrants.len() < 2 {
+        return Err(amethyst::Error::from_string("a tournament needs at least two entrants".to_string()));
+    }
+    if config.games == 0 || config.threads == 0 {
+        return Err(amethyst::Error::from_string("`--games` and `--threads` have to be above 0".to_string()));
+    }
+    Ok(config)
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> amethyst::Result<String> {
+    args.next()
+        .ok_or_else(|| amethyst::Error::from_string(format!("`{}` expects a value", flag)))
+}
+
+fn parse_side(value: &str) -> amethyst::Result<Side> {
+    match value {
+        "left" => Ok(Side::Left),
+        "right" => Ok(Side::Right),
+        other => Err(amethyst::Error::from_string(format!("unknown side `{}`, expected left or right", other))),
+    }
+}
+
+fn parse_controller(value: &str) -> amethyst::Result<ControllerChoice> {
+    value.parse().map_err(amethyst::Error::from_string)
+}
+
+fn seconds(value: &str) -> amethyst::Result<f32> {
+    parse_seconds(value).map_err(amethyst::Error::from_string)
+}
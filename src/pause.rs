@@ -0,0 +1,50 @@
+use amethyst::{
+    ecs::prelude::Entity,
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
+    prelude::*,
+};
+
+use crate::menu::MainMenu;
+use crate::pong::load_font;
+use crate::ui::{create_text_line, delete_entities};
+
+// Paused is pushed on top of the match. The match underneath doesn't update, so nothing moves
+// until this state is popped again.
+#[derive(Default)]
+pub struct Paused {
+    ui: Vec<Entity>,
+}
+
+impl SimpleState for Paused {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        let font = load_font(world);
+
+        self.ui = vec![
+            create_text_line(world, &font, "Paused", 40., 20.),
+            create_text_line(world, &font, "Esc: resume   M: quit to menu", 20., -30.),
+        ];
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn handle_event(&mut self, _data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+            if is_key_down(event, VirtualKeyCode::Escape) || is_key_down(event, VirtualKeyCode::P) {
+                return Trans::Pop;
+            }
+            // not Q: it moves the left paddle, and key repeat from a held paddle key would
+            // throw the match away
+            if is_key_down(event, VirtualKeyCode::M) {
+                // leave the pause overlay, then replace the match with the menu
+                return Trans::Sequence(vec![Trans::Pop, Trans::Switch(Box::new(MainMenu::default()))]);
+            }
+        }
+        Trans::None
+    }
+}
@@ -1,20 +1,32 @@
 use amethyst::{
     assets::{AssetStorage, Loader, Handle},
-    core::{timing::Time, transform::Transform},
-    ecs::prelude::{Component, DenseVecStorage, Entity},
+    core::{math::Vector3, timing::Time, transform::Transform},
+    ecs::prelude::{Component, DenseVecStorage, Dispatcher, Entity},
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
     prelude::*,
     renderer::{Camera, ImageFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture},
-    ui::{Anchor, TtfFormat, UiText, UiTransform},
+    ui::{Anchor, FontHandle, TtfFormat, UiText, UiTransform},
 };
 
+use std::ops::{Deref, DerefMut};
+
+use pong_core::{FixedTimestep, GameConfig, MatchRules, MatchState, PhysicsConfig, Serve};
+
 use crate::audio::initialize_audio;
+use crate::controller::Controller;
+use crate::game_over::GameOver;
+use crate::options::{MatchSeed, Players};
+use crate::instant_replay::{History, InstantReplay};
+use crate::pause::Paused;
+use crate::replay::{save_recording, start_recording};
+use crate::systems::physics_dispatcher;
 
-// ScoreBoard contains the actual score data
-#[derive(Default)]
-pub struct ScoreBoard {
-    pub score_left: i32,
-    pub score_right: i32,
-}
+pub use pong_core::{ScoreBoard, Side};
+
+// size of the paddle and ball sprites in the sprite sheet; they are scaled to the sizes in
+// `GameConfig`
+const PADDLE_SPRITE_SIZE: [f32; 2] = [4.0, 16.0];
+const BALL_SPRITE_SIZE: f32 = 4.0;
 
 // ScoreText contains the ui text components that display the score
 pub struct ScoreText {
@@ -22,44 +34,48 @@ pub struct ScoreText {
     pub p2_score: Entity,
 }
 
+// ServeText is the ui text that counts down to the next serve
+pub struct ServeText {
+    pub text: Entity,
+}
+
 #[derive(Default)]
 pub struct Pong {
-    ball_spawn_timer: Option<f32>,
     sprite_sheet_handle: Option<Handle<SpriteSheet>>,
+    physics: Option<Dispatcher<'static, 'static>>,
 }
 
-pub const ARENA_HEIGHT: f32 = 100.0;
-pub const ARENA_WIDTH: f32 = 100.0;
-pub const PADDLE_HEIGHT: f32 = 16.0;
-pub const PADDLE_WIDTH: f32 = 4.0;
-pub const BALL_VELOCITY_X: f32 = 70.0;
-pub const BALL_VELOCITY_Y: f32 = 50.0;
-pub const BALL_RADIUS: f32 = 2.0;
+// Paddle and Ball wrap the pong-core types so they can be stored as components. The core
+// structs hold the positions; the systems copy them into each entity's Transform.
+pub struct Paddle(pub pong_core::Paddle);
+
+pub struct Ball(pub pong_core::Ball);
 
-#[derive(PartialEq, Eq)]
-pub enum Side {
-    Left,
-    Right
+impl Deref for Paddle {
+    type Target = pong_core::Paddle;
+
+    fn deref(&self) -> &pong_core::Paddle {
+        &self.0
+    }
 }
 
-pub struct Paddle {
-    pub side: Side,
-    pub width: f32,
-    pub height: f32
+impl DerefMut for Paddle {
+    fn deref_mut(&mut self) -> &mut pong_core::Paddle {
+        &mut self.0
+    }
 }
 
-pub struct Ball {
-    pub velocity: [f32; 2],
-    pub radius: f32
+impl Deref for Ball {
+    type Target = pong_core::Ball;
+
+    fn deref(&self) -> &pong_core::Ball {
+        &self.0
+    }
 }
 
-impl Paddle {
-    fn new(side: Side) -> Paddle {
-        Paddle {
-            side,
-            width: PADDLE_WIDTH,
-            height: PADDLE_HEIGHT
-        }
+impl DerefMut for Ball {
+    fn deref_mut(&mut self) -> &mut pong_core::Ball {
+        &mut self.0
     }
 }
 
@@ -77,105 +93,189 @@ impl SimpleState for Pong {
         // no longer needed now that a system uses the Paddle component
         // world.register::<Paddle>();
         // world.register::<Ball>();
-        self.ball_spawn_timer.replace(1.0);
         self.sprite_sheet_handle.replace(load_sprite_sheet(world));
+        reset_match(world);
+        self.physics.replace(physics_dispatcher(world));
 
-        initialize_paddles(world, self.sprite_sheet_handle.clone().unwrap());
+        initialize_paddles(world, self.sprite_sheet_handle.clone());
+        initialize_ball(world, self.sprite_sheet_handle.clone());
         initialize_camera(world);
         initialize_scoreboard(world);
         initialize_audio(world);
     }
-    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
-        if let Some(mut timer) = self.ball_spawn_timer.take() {
-            {
-                let time = data.world.fetch::<Time>();
-                timer -= time.delta_seconds();
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        save_recording(data.world);
+        // the next state builds its own scene
+        data.world.delete_all();
+    }
+
+    fn handle_event(&mut self, _data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
             }
-            if timer <= 0.0 {
-                // time expired
-                initialize_ball(data.world, self.sprite_sheet_handle.clone().unwrap());
-            } else {
-                self.ball_spawn_timer.replace(timer);
+            if is_key_down(event, VirtualKeyCode::Escape) || is_key_down(event, VirtualKeyCode::P) {
+                return Trans::Push(Box::new(Paused::default()));
+            }
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        // run as many fixed physics ticks as this frame's time allows
+        let ticks = {
+            let time = data.world.fetch::<Time>();
+            data.world.write_resource::<FixedTimestep>().advance(time.delta_seconds())
+        };
+        if let Some(physics) = self.physics.as_mut() {
+            for _ in 0..ticks {
+                physics.dispatch(data.world);
             }
         }
+
+        let winner = data.world.read_resource::<MatchState>().winner;
+        if let Some(winner) = winner {
+            let scores = data.world.read_resource::<ScoreBoard>().clone();
+            return Trans::Switch(Box::new(GameOver::new(winner, scores)));
+        }
+
+        // show how the point was won before the next serve, unless rules.ron turns that off
+        let last_point = data.world.write_resource::<History>().take_last_point();
+        let instant_replay = data.world.read_resource::<MatchRules>().instant_replay;
+        if let (Some(frames), true) = (last_point, instant_replay) {
+            return Trans::Push(Box::new(InstantReplay::new(frames)));
+        }
         Trans::None
     }
 }
 
-fn initialize_camera(world: &mut World) {
+// a rematch starts from scratch: no points, no time played and the first serve
+pub fn reset_match(world: &mut World) {
+    let seed = world.read_resource::<MatchSeed>().0;
+    let serve = Serve::new(seed, &world.read_resource::<MatchRules>());
+    world.insert(serve);
+    world.insert(ScoreBoard::default());
+    world.insert(MatchState::default());
+    world.insert(History::default());
+    start_recording(world, seed);
+}
+
+pub fn initialize_camera(world: &mut World) {
     // Setup camera so screen covers whole arena and (0,0) is bottom left.
+    let (width, height) = {
+        let config = world.read_resource::<GameConfig>();
+        (config.arena_width, config.arena_height)
+    };
     let mut transform = Transform::default();
-    transform.set_translation_xyz(ARENA_WIDTH * 0.5, ARENA_HEIGHT * 0.5, 1.0);
+    transform.set_translation_xyz(width * 0.5, height * 0.5, 1.0);
 
     world
         .create_entity()
-        .with(Camera::standard_2d(ARENA_WIDTH, ARENA_HEIGHT))
+        .with(Camera::standard_2d(width, height))
         .with(transform)
         .build();
 }
 
 
-fn initialize_paddles(world: &mut World, sprite_sheet_handle: Handle<SpriteSheet>) {
-    let sprite_render = SpriteRender {
-        sprite_sheet: sprite_sheet_handle,
+// paddles are drawn with the sprite sheet if one is given; headless runs pass None
+pub fn initialize_paddles(world: &mut World, sprite_sheet_handle: Option<Handle<SpriteSheet>>) {
+    let sprite_render = sprite_sheet_handle.map(|sprite_sheet| SpriteRender {
+        sprite_sheet,
         sprite_number: 0, // paddle is the first sprite
-    };
+    });
 
-    let mut left_transform = Transform::default();
-    let mut right_transform = Transform::default();
+    let players = world.read_resource::<Players>().clone();
+    let left_controller = Controller::new(&players.left, Side::Left);
+    let right_controller = Controller::new(&players.right, Side::Right);
 
     // position paddles 
-    let y = ARENA_HEIGHT / 2.0;
-    left_transform.set_translation_xyz(PADDLE_WIDTH * 0.5, y, 0.0);
-    right_transform.set_translation_xyz(ARENA_WIDTH - PADDLE_WIDTH * 0.5, y, 0.0);
+    let config = world.read_resource::<GameConfig>().clone();
+    let left_paddle = pong_core::Paddle::new(Side::Left, &config);
+    let right_paddle = pong_core::Paddle::new(Side::Right, &config);
+
+    let mut left_transform = Transform::default();
+    let mut right_transform = Transform::default();
+    left_transform.set_translation_xyz(left_paddle.position[0], left_paddle.position[1], 0.0);
+    right_transform.set_translation_xyz(right_paddle.position[0], right_paddle.position[1], 0.0);
+    left_transform.set_scale(paddle_scale(&config));
+    right_transform.set_scale(paddle_scale(&config));
 
     // create left plank entity
-    world
+    let mut left = world
         .create_entity()
-        .with(sprite_render.clone())
-        .with(Paddle::new(Side::Left))
-        .with(left_transform)
-        .build();
+        .with(Paddle(left_paddle))
+        .with(left_controller)
+        .with(left_transform);
+    if let Some(sprite_render) = sprite_render.clone() {
+        left = left.with(sprite_render);
+    }
+    left.build();
 
     // create right plank entity
-    world
+    let mut right = world
         .create_entity()
-        .with(sprite_render)
-        .with(Paddle::new(Side::Right))
-        .with(right_transform)
-        .build();
+        .with(Paddle(right_paddle))
+        .with(right_controller)
+        .with(right_transform);
+    if let Some(sprite_render) = sprite_render {
+        right = right.with(sprite_render);
+    }
+    right.build();
 }
 
-// initializes one ball in the middle-ish of the arena
-fn initialize_ball(world: &mut World, sprite_sheet_handle: Handle<SpriteSheet>) {
+// initializes one ball in the middle of the arena, held there until it is served
+pub fn initialize_ball(world: &mut World, sprite_sheet_handle: Option<Handle<SpriteSheet>>) {
+    let speed = world.read_resource::<PhysicsConfig>().ball_speed;
+    let config = world.read_resource::<GameConfig>().clone();
+    let mut ball = pong_core::Ball::new(&config, speed);
+    ball.hold(ball.position);
+
     // create the translation
     let mut local_transform = Transform::default();
-    local_transform.set_translation_xyz(ARENA_WIDTH / 2.0, ARENA_HEIGHT / 2.0, 0.0);
+    local_transform.set_translation_xyz(ball.position[0], ball.position[1], 0.0);
+    local_transform.set_scale(ball_scale(&config));
+
+    let mut ball = world
+        .create_entity()
+        .with(Ball(ball))
+        .with(local_transform);
 
     // assign the sprite
-    let sprite_render = SpriteRender {
-        sprite_sheet: sprite_sheet_handle,
-        sprite_number: 1, // index = 1 for ball in sprite sheet
-    };
+    if let Some(sprite_sheet) = sprite_sheet_handle {
+        ball = ball.with(SpriteRender {
+            sprite_sheet,
+            sprite_number: 1, // index = 1 for ball in sprite sheet
+        });
+    }
+    ball.build();
+}
 
-    world
-        .create_entity()
-        .with(sprite_render)
-        .with(Ball {
-            radius: BALL_RADIUS,
-            velocity: [BALL_VELOCITY_X, BALL_VELOCITY_Y]
-        })
-        .with(local_transform)
-        .build();
+// scale that stretches the paddle sprite to the configured paddle size
+pub fn paddle_scale(config: &GameConfig) -> Vector3<f32> {
+    Vector3::new(
+        config.paddle_width / PADDLE_SPRITE_SIZE[0],
+        config.paddle_height / PADDLE_SPRITE_SIZE[1],
+        1.0,
+    )
+}
+
+pub fn ball_scale(config: &GameConfig) -> Vector3<f32> {
+    let scale = config.ball_radius * 2.0 / BALL_SPRITE_SIZE;
+    Vector3::new(scale, scale, 1.0)
 }
 
-fn initialize_scoreboard(world: &mut World) {
-    let font = world.read_resource::<Loader>().load(
+pub fn load_font(world: &mut World) -> FontHandle {
+    world.read_resource::<Loader>().load(
         "font/square.ttf",
         TtfFormat,
         (),
         &world.read_resource(),
-    );
+    )
+}
+
+pub fn initialize_scoreboard(world: &mut World) {
+    let font = load_font(world);
     let p1_transform = UiTransform::new(
         "P1".to_string(), Anchor::TopMiddle, Anchor::TopMiddle,
         -50., -50., 1., 200., 50.,
@@ -199,13 +299,24 @@ fn initialize_scoreboard(world: &mut World) {
     let p2_score = world
         .create_entity()
         .with(p2_transform)
-        .with(UiText::new(font, "0".to_string(), [1., 1., 1., 1.], 50.))
+        .with(UiText::new(font.clone(), "0".to_string(), [1., 1., 1., 1.], 50.))
         .build();
 
     world.insert(ScoreText { p1_score, p2_score });
+
+    let serve_transform = UiTransform::new(
+        "serve".to_string(), Anchor::BottomMiddle, Anchor::BottomMiddle,
+        0., 20., 1., 300., 25.,
+    );
+    let text = world
+        .create_entity()
+        .with(serve_transform)
+        .with(UiText::new(font, String::new(), [1., 1., 1., 1.], 25.))
+        .build();
+    world.insert(ServeText { text });
 }
 
-fn load_sprite_sheet(world: &mut World) -> Handle<SpriteSheet> {
+pub fn load_sprite_sheet(world: &mut World) -> Handle<SpriteSheet> {
     // load the sprite sheet to render the graphics
     // the texture is the pixel data
     // `texture_handle` is a cloneable reference to the texture
@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::PathBuf;
+
+use amethyst::{
+    log::{error, info},
+    prelude::*,
+};
+use pong_core::replay::config_hash;
+use pong_core::{GameConfig, Input, MatchRules, PhysicsConfig, Replay};
+
+// Recorder collects every tick's input while `--record` is given. Each match starts a new
+// recording, which is written out when the match's state stops or a rematch starts. The first
+// match goes to the path given, later ones get a number: match.replay, match-2.replay, ...
+pub struct Recorder {
+    path: PathBuf,
+    replay: Option<Replay>,
+    // matches written so far
+    saved: u32,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf) -> Recorder {
+        Recorder {
+            path,
+            replay: None,
+            saved: 0,
+        }
+    }
+
+    // where the next match is written
+    fn next_path(&self) -> PathBuf {
+        if self.saved == 0 {
+            return self.path.clone();
+        }
+        let stem = self.path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+        let mut name = format!("{}-{}", stem, self.saved + 1);
+        if let Some(extension) = self.path.extension() {
+            name.push('.');
+            name.push_str(&extension.to_string_lossy());
+        }
+        self.path.with_file_name(name)
+    }
+
+    pub fn start(&mut self, seed: u64, config_hash: u64) {
+        self.replay = Some(Replay::new(seed, config_hash));
+    }
+
+    // true from the start of a match until it is written out
+    pub fn is_recording(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    pub fn record(&mut self, input: Input) {
+        if let Some(ref mut replay) = self.replay {
+            replay.inputs.push(input);
+        }
+    }
+
+    // writes out the recording, returning where to and how many ticks it has
+    fn save(&mut self) -> io::Result<Option<(PathBuf, usize)>> {
+        match self.replay.take() {
+            Some(replay) => {
+                let path = self.next_path();
+                replay.write_to(BufWriter::new(File::create(&path)?))?;
+                self.saved += 1;
+                Ok(Some((path, replay.inputs.len())))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+pub fn load_replay(path: &str) -> amethyst::Result<Replay> {
+    let file = File::open(path)
+        .map_err(|e| amethyst::Error::from_string(format!("failed to open replay `{}`: {}", path, e)))?;
+    Replay::read_from(BufReader::new(file))
+        .map_err(|e| amethyst::Error::from_string(format!("failed to read replay `{}`: {}", path, e)))
+}
+
+// the hash of the configuration in the world, which a replay has to match to play back the same
+pub fn world_config_hash(world: &World) -> u64 {
+    config_hash(
+        &world.read_resource::<GameConfig>(),
+        &world.read_resource::<PhysicsConfig>(),
+        &world.read_resource::<MatchRules>(),
+    )
+}
+
+// starts recording a new match, if recording at all, after writing out the one before it
+pub fn start_recording(world: &mut World, seed: u64) {
+    save_recording(world);
+    let hash = world_config_hash(world);
+    if let Some(mut recorder) = world.try_fetch_mut::<Recorder>() {
+        recorder.start(seed, hash);
+    }
+}
+
+// writes out the match recorded so far, if any
+pub fn save_recording(world: &mut World) {
+    if let Some(mut recorder) = world.try_fetch_mut::<Recorder>() {
+        match recorder.save() {
+            Ok(Some((path, ticks))) => info!("recorded {} ticks to {}", ticks, path.display()),
+            Ok(None) => {}
+            Err(e) => error!("failed to write replay {}: {}", recorder.next_path().display(), e),
+        }
+    }
+}
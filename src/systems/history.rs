@@ -0,0 +1,45 @@
+use amethyst::{
+    core::SystemDesc,
+    derive::SystemDesc,
+    ecs::prelude::{Join, ReadExpect, ReadStorage, System, SystemData, World, Write},
+};
+use pong_core::{FixedTimestep, Side};
+
+use crate::instant_replay::{Frame, History, INSTANT_REPLAY_SECONDS};
+use crate::pong::{Ball, Paddle};
+
+// HistorySystem remembers where the ball and paddles were on each of the last few seconds of
+// ticks, for the instant replay. It runs before the winner system so the last frame before a
+// point still has the ball at the edge of the arena.
+#[derive(SystemDesc)]
+pub struct HistorySystem;
+
+impl<'s> System<'s> for HistorySystem {
+    type SystemData = (
+        ReadStorage<'s, Ball>,
+        ReadStorage<'s, Paddle>,
+        Write<'s, History>,
+        ReadExpect<'s, FixedTimestep>,
+    );
+
+    fn run(&mut self, (balls, paddles, mut history, clock): Self::SystemData) {
+        let ball = match (&balls).join().next() {
+            Some(ball) => ball.position,
+            None => return,
+        };
+        let mut frame = Frame {
+            ball,
+            left: [0.0, 0.0],
+            right: [0.0, 0.0],
+        };
+        for paddle in (&paddles).join() {
+            match paddle.side {
+                Side::Left => frame.left = paddle.position,
+                Side::Right => frame.right = paddle.position,
+            }
+        }
+
+        let capacity = (INSTANT_REPLAY_SECONDS / clock.tick_seconds()).ceil() as usize;
+        history.push(frame, capacity);
+    }
+}
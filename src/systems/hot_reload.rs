@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use amethyst::{
+    assets::{AssetStorage, Loader},
+    audio::AudioSink,
+    config::Config,
+    core::{timing::Time, transform::Transform},
+    ecs::prelude::{
+        Entities, Entity, Join, Read, ReadExpect, System, Write, WriteExpect, WriteStorage,
+    },
+    input::{Bindings, InputHandler, StringBindings},
+    log::{error, info},
+    renderer::Camera,
+    ui::{Anchor, FontAsset, LineMode, TtfFormat, UiText, UiTransform},
+    window::{DisplayConfig, Window},
+    winit::dpi::LogicalSize,
+};
+use pong_core::{AudioConfig, FixedTimestep, GameConfig, MatchRules, PhysicsConfig};
+
+use crate::netplay::NetMatch;
+use crate::pong::{ball_scale, paddle_scale, Ball, Paddle};
+use crate::replay::Recorder;
+
+// how often the config files are checked for changes, in seconds
+const POLL_INTERVAL: f32 = 0.5;
+
+const WATCHED: [&str; 6] = [
+    "game.ron",
+    "physics.ron",
+    "rules.ron",
+    "audio.ron",
+    "bindings.ron",
+    "display.ron",
+];
+
+// the files a match is played by; a replay or the other side of a network match only plays
+// back the same with the settings the match started with
+const MATCH_SETTINGS: [&str; 3] = ["game.ron", "physics.ron", "rules.ron"];
+
+// ConfigErrors holds why each config file that failed to reload was rejected, by file name. A
+// file drops out once it loads again.
+#[derive(Default)]
+pub struct ConfigErrors(pub BTreeMap<&'static str, String>);
+
+// HotReloadSystem watches the files in `config/` while the game runs and applies any that
+// change. A file that fails to parse is reported and ignored, so the last good values stay in
+// effect until it is fixed. Changes to the match settings wait while a match is recorded or
+// played over the network, and are applied after it.
+pub struct HotReloadSystem {
+    dir: PathBuf,
+    modified: Vec<Option<SystemTime>>,
+    since_poll: f32,
+}
+
+impl HotReloadSystem {
+    pub fn new(dir: PathBuf) -> HotReloadSystem {
+        // the files as they are now were loaded at startup
+        let modified = WATCHED.iter().map(|file| modified_time(&dir.join(file))).collect();
+        HotReloadSystem {
+            dir,
+            modified,
+            since_poll: 0.0,
+        }
+    }
+}
+
+impl<'s> System<'s> for HotReloadSystem {
+    type SystemData = (
+        Read<'s, Time>,
+        WriteExpect<'s, GameConfig>,
+        WriteExpect<'s, PhysicsConfig>,
+        WriteExpect<'s, MatchRules>,
+        Write<'s, AudioConfig>,
+        WriteExpect<'s, FixedTimestep>,
+        Write<'s, InputHandler<StringBindings>>,
+        Option<WriteExpect<'s, AudioSink>>,
+        Option<ReadExpect<'s, Window>>,
+        WriteStorage<'s, Paddle>,
+        WriteStorage<'s, Ball>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Camera>,
+        Write<'s, ConfigErrors>,
+        Option<Read<'s, Recorder>>,
+        Option<Read<'s, NetMatch>>,
+    );
+
+    fn run(&mut self, (
+            time,
+            mut game_config,
+            mut physics,
+            mut rules,
+            mut audio,
+            mut clock,
+            mut input,
+            mut sink,
+            window,
+            mut paddles,
+            mut balls,
+            mut transforms,
+            mut cameras,
+            mut errors,
+            recorder,
+            net_match,
+        ): Self::SystemData) {
+        self.since_poll += time.delta_real_seconds();
+        if self.since_poll < POLL_INTERVAL {
+            return;
+        }
+        self.since_poll = 0.0;
+
+        let locked_by = if net_match.is_some() {
+            Some("a network match")
+        } else if recorder.is_some_and(|recorder| recorder.is_recording()) {
+            Some("a match being recorded")
+        } else {
+            None
+        };
+
+        for (i, &file) in WATCHED.iter().enumerate() {
+            let path = self.dir.join(file);
+            let modified = modified_time(&path);
+            if modified == self.modified[i] {
+                continue;
+            }
+            if let (Some(locked_by), true) = (locked_by, MATCH_SETTINGS.contains(&file)) {
+                // the file still looks changed on the next poll, until the match is over
+                let message = format!("not reloaded during {}, it applies after the match", locked_by);
+                if errors.0.get(file) != Some(&message) {
+                    info!("config/{} changed; {}", file, message);
+                    errors.0.insert(file, message);
+                }
+                continue;
+            }
+            self.modified[i] = modified;
+
+            let result = match file {
+                "game.ron" => load::<GameConfig>(&path).map(|config| {
+                    for paddle in (&mut paddles).join() {
+                        paddle.resize(&config);
+                    }
+                    for ball in (&mut balls).join() {
+                        ball.radius = config.ball_radius;
+                    }
+                    for (_, transform) in (&paddles, &mut transforms).join() {
+                        transform.set_scale(paddle_scale(&config));
+                    }
+                    for (_, transform) in (&balls, &mut transforms).join() {
+                        transform.set_scale(ball_scale(&config));
+                    }
+                    let (width, height) = (config.arena_width, config.arena_height);
+                    for (camera, transform) in (&mut cameras, &mut transforms).join() {
+                        *camera = Camera::standard_2d(width, height);
+                        transform.set_translation_xyz(width * 0.5, height * 0.5, 1.0);
+                    }
+                    *game_config = config;
+                }),
+                "physics.ron" => load::<PhysicsConfig>(&path).map(|config| {
+                    if config.tick_rate != physics.tick_rate {
+                        *clock = FixedTimestep::new(config.tick_rate);
+                    }
+                    *physics = config;
+                }),
+                "rules.ron" => load::<MatchRules>(&path).map(|config| *rules = config),
+                "audio.ron" => load::<AudioConfig>(&path).map(|config| {
+                    if let Some(ref mut sink) = sink {
+                        sink.set_volume(config.music_volume);
+                    }
+                    *audio = config;
+                }),
+                "bindings.ron" => {
+                    load::<Bindings<StringBindings>>(&path).map(|bindings| input.bindings = bindings)
+                }
+                _ => load::<DisplayConfig>(&path).map(|display| {
+                    if let Some(ref window) = window {
+                        window.set_title(&display.title);
+                        if let Some((width, height)) = display.dimensions {
+                            let size = LogicalSize::new(f64::from(width), f64::from(height));
+                            window.set_inner_size(size);
+                        }
+                    }
+                }),
+            };
+
+            match result {
+                Ok(()) => {
+                    info!("reloaded config/{}", file);
+                    errors.0.remove(file);
+                }
+                Err(message) => {
+                    error!("keeping the previous config/{}: {}", file, message);
+                    errors.0.insert(file, message);
+                }
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn load<T: Config>(path: &Path) -> Result<T, String> {
+    T::load(path).map_err(|e| e.to_string())
+}
+
+// ConfigErrorSystem shows the current config errors in the bottom left corner of the screen
+#[derive(Default)]
+pub struct ConfigErrorSystem {
+    text: Option<Entity>,
+}
+
+impl<'s> System<'s> for ConfigErrorSystem {
+    type SystemData = (
+        Entities<'s>,
+        Read<'s, ConfigErrors>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, UiTransform>,
+        ReadExpect<'s, Loader>,
+        Read<'s, AssetStorage<FontAsset>>,
+    );
+
+    fn run(&mut self, (entities, errors, mut ui_text, mut ui_transforms, loader, fonts): Self::SystemData) {
+        let message = errors
+            .0
+            .iter()
+            .map(|(file, error)| format!("config/{}: {}", file, error))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // states clear out the world when they stop, which takes the text with them
+        let text = match self.text.filter(|&text| entities.is_alive(text)) {
+            Some(text) => text,
+            None if message.is_empty() => return,
+            None => {
+                let font = loader.load("font/square.ttf", TtfFormat, (), &fonts);
+                let mut text = UiText::new(font, String::new(), [1., 0.3, 0.3, 1.], 12.);
+                text.line_mode = LineMode::Wrap;
+                text.align = Anchor::BottomLeft;
+                let transform = UiTransform::new(
+                    "config_errors".to_string(), Anchor::BottomLeft, Anchor::BottomLeft,
+                    10., 10., 2., 480., 120.,
+                );
+                let entity = entities
+                    .build_entity()
+                    .with(transform, &mut ui_transforms)
+                    .with(text, &mut ui_text)
+                    .build();
+                self.text = Some(entity);
+                entity
+            }
+        };
+
+        if let Some(text) = ui_text.get_mut(text) {
+            if text.text != message {
+                text.text = message;
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use amethyst::{
+    core::transform::Transform,
+    core::SystemDesc,
+    derive::SystemDesc,
+    ecs::prelude::{Join, Read, ReadExpect, ReadStorage, System, SystemData, World, WriteStorage},
+};
+use pong_core::{FixedTimestep, Side};
+
+use crate::instant_replay::ShownFrame;
+use crate::pong::{Ball, Paddle};
+
+// InterpolationSystem runs once per rendered frame, after the physics ticks. It places each
+// ball and paddle between its last two simulated positions so motion stays smooth when the
+// frame rate and the tick rate differ. During an instant replay it shows the replayed frame
+// instead.
+#[derive(SystemDesc)]
+pub struct InterpolationSystem;
+
+impl<'s> System<'s> for InterpolationSystem {
+    type SystemData = (
+        ReadStorage<'s, Ball>,
+        ReadStorage<'s, Paddle>,
+        WriteStorage<'s, Transform>,
+        ReadExpect<'s, FixedTimestep>,
+        Read<'s, ShownFrame>,
+    );
+
+    fn run(&mut self, (balls, paddles, mut transforms, clock, shown): Self::SystemData) {
+        if let Some(ref frame) = shown.0 {
+            for (_, transform) in (&balls, &mut transforms).join() {
+                transform.set_translation_x(frame.ball[0]);
+                transform.set_translation_y(frame.ball[1]);
+            }
+            for (paddle, transform) in (&paddles, &mut transforms).join() {
+                let [x, y] = match paddle.side {
+                    Side::Left => frame.left,
+                    Side::Right => frame.right,
+                };
+                transform.set_translation_x(x);
+                transform.set_translation_y(y);
+            }
+            return;
+        }
+
+        let alpha = clock.alpha();
+
+        for (ball, transform) in (&balls, &mut transforms).join() {
+            let [x, y] = ball.interpolated_position(alpha);
+            transform.set_translation_x(x);
+            transform.set_translation_y(y);
+        }
+
+        for (paddle, transform) in (&paddles, &mut transforms).join() {
+            let [x, y] = paddle.interpolated_position(alpha);
+            transform.set_translation_x(x);
+            transform.set_translation_y(y);
+        }
+    }
+}
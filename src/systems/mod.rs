@@ -1,9 +1,39 @@
-pub use self::paddle::PaddleSystem;
+pub use self::paddle::{Devices, PaddleSystem};
 pub use self::bounce::BounceSystem;
 pub use self::move_balls::MoveBallsSystem;
+pub use self::serve::{serve_label, ServeSystem};
 pub use self::winner::WinnerSystem;
+pub use self::interpolate::InterpolationSystem;
+pub use self::history::HistorySystem;
+pub use self::hot_reload::{ConfigErrorSystem, HotReloadSystem};
 
 mod paddle;
 mod move_balls;
+mod serve;
 mod bounce;
 mod winner;
+mod interpolate;
+mod history;
+mod hot_reload;
+
+use amethyst::{
+    core::ArcThreadPool,
+    ecs::prelude::{Dispatcher, DispatcherBuilder, World, WorldExt},
+};
+
+// The gameplay systems don't run with the per-frame game data. The playing states dispatch them
+// once per fixed physics tick instead, see `pong_core::FixedTimestep`. Serve runs between the
+// paddles and the ball, and winner after collision, so the order matches `pong_core::Arena::step`.
+pub fn physics_dispatcher(world: &mut World) -> Dispatcher<'static, 'static> {
+    let mut dispatcher = DispatcherBuilder::new()
+        .with_pool(world.read_resource::<ArcThreadPool>().clone())
+        .with(PaddleSystem, "paddle_system", &[])
+        .with(ServeSystem, "serve_system", &["paddle_system"])
+        .with(MoveBallsSystem, "ball_system", &["serve_system"])
+        .with(BounceSystem, "collision_system", &["paddle_system", "ball_system"])
+        .with(HistorySystem, "history_system", &["collision_system"])
+        .with(WinnerSystem, "winner_system", &["history_system"])
+        .build();
+    dispatcher.setup(world);
+    dispatcher
+}
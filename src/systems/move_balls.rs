@@ -1,10 +1,9 @@
 use amethyst::{
-    core::timing::Time,
-    core::transform::Transform,
     core::SystemDesc,
     derive::SystemDesc,
-    ecs::prelude::{Join, Read, ReadStorage, System, SystemData, World, WriteStorage},
+    ecs::prelude::{Join, ReadExpect, System, SystemData, World, WriteStorage},
 };
+use pong_core::{FixedTimestep, PhysicsConfig};
 
 use crate::pong::Ball;
 
@@ -13,16 +12,14 @@ pub struct MoveBallsSystem;
 
 impl<'s> System<'s> for MoveBallsSystem {
     type SystemData = (
-        ReadStorage<'s, Ball>,
-        WriteStorage<'s, Transform>,
-        Read<'s, Time>,
+        WriteStorage<'s, Ball>,
+        ReadExpect<'s, FixedTimestep>,
+        ReadExpect<'s, PhysicsConfig>,
     );
 
-    fn run(&mut self, (balls, mut locals, time): Self::SystemData) {
-        for (ball, local) in (&balls, &mut locals).join() {
-            local.prepend_translation_x(ball.velocity[0] * time.delta_seconds());
-            local.prepend_translation_y(ball.velocity[1] * time.delta_seconds());
+    fn run(&mut self, (mut balls, clock, physics): Self::SystemData) {
+        for ball in (&mut balls).join() {
+            ball.advance(clock.tick_seconds(), &physics);
         }
     }
 }
-
@@ -1,46 +1,148 @@
-use amethyst::core::{Transform, SystemDesc};
+use amethyst::core::SystemDesc;
 use amethyst::derive::SystemDesc;
-use amethyst::ecs::{Join, Read, ReadStorage, System, SystemData, World, WriteStorage};
-use amethyst::input::{InputHandler, StringBindings};
+use amethyst::ecs::{Join, Read, ReadExpect, ReadStorage, System, SystemData, World, Write, WriteExpect, WriteStorage};
+use amethyst::input::{ControllerAxis, InputHandler, StringBindings};
+use amethyst::window::ScreenDimensions;
+use pong_core::{ControlContext, FixedTimestep, GameConfig, Input, InputSource, ScoreBoard, Side};
 
-use crate::pong::{Paddle, Side, ARENA_HEIGHT, PADDLE_HEIGHT};
+use crate::controller::Controller;
+use crate::pong::{Ball, Paddle};
+use crate::replay::Recorder;
+use crate::tui::TerminalKeys;
 
+// PaddleSystem asks each paddle's controller whether to serve and how far to move, and moves the
+// paddle. What the controllers chose is left in the `Input` resource for ServeSystem, and
+// recorded when a recording is running.
 #[derive(SystemDesc)]
 pub struct PaddleSystem;
 
 impl<'s> System<'s> for PaddleSystem {
     type SystemData = (
-        WriteStorage<'s, Transform>,
-        ReadStorage<'s, Paddle>,
+        WriteStorage<'s, Paddle>,
+        WriteStorage<'s, Controller>,
+        ReadStorage<'s, Ball>,
         Read<'s, InputHandler<StringBindings>>,
+        Option<ReadExpect<'s, ScreenDimensions>>,
+        ReadExpect<'s, FixedTimestep>,
+        ReadExpect<'s, GameConfig>,
+        Read<'s, ScoreBoard>,
+        Write<'s, Input>,
+        Option<WriteExpect<'s, Recorder>>,
+        Option<ReadExpect<'s, TerminalKeys>>,
     );
 
-    fn run(&mut self, (mut transforms, paddles, input): Self::SystemData) {
-        for (paddle, transform) in (&paddles, &mut transforms).join() {
-            let movement = match paddle.side {
-                Side::Left => input.axis_value("left_paddle"),
-                Side::Right => input.axis_value("right_paddle"),
+    fn run(&mut self, (
+            mut paddles,
+            mut controllers,
+            balls,
+            input,
+            screen,
+            clock,
+            config,
+            scores,
+            mut tick_input,
+            recorder,
+            terminal_keys,
+        ): Self::SystemData) {
+        let ball = (&balls).join().next().map(|ball| &ball.0);
+        let devices = Devices::new(&input, screen.as_ref().map(|s| &**s), config.arena_height)
+            .with_terminal_keys(terminal_keys.as_ref().map(|keys| &**keys));
+
+        // where the paddles were before any of them moved this tick, for their opponents
+        let before: Vec<pong_core::Paddle> = (&paddles).join().map(|paddle| paddle.0.clone()).collect();
+
+        let mut chosen = Input::default();
+        for (paddle, controller) in (&mut paddles, &mut controllers).join() {
+            let (serve, movement) = {
+                let context = ControlContext {
+                    paddle,
+                    opponent: before.iter().find(|other| other.side != paddle.side),
+                    ball,
+                    arena_height: config.arena_height,
+                    scores: &scores,
+                    dt: clock.tick_seconds(),
+                    input: &devices,
+                };
+                (controller.0.serve(&context), controller.0.movement(&context))
             };
 
-            if let Some(mv_amount) = movement {
-                if mv_amount != 0.0 {
-                    let side_name = match paddle.side {
-                        Side::Left => "left",
-                        Side::Right => "right",
-                    };
-                    // println!("Side {:?} moving {}", side_name, mv_amount);
-                    // TODO: use amethyst::core::timing::Time instead of our factor of 1.2
-                    let scaled_amount = 1.2 * mv_amount as f32;
-                    let paddle_y = transform.translation().y;
-                    transform.set_translation_y(
-                        (paddle_y + scaled_amount)
-                        .min(ARENA_HEIGHT - PADDLE_HEIGHT * 0.5)
-                        .max(PADDLE_HEIGHT * 0.5),
-                    );
+            paddle.apply_input(movement, config.arena_height, clock.tick_seconds());
+            match paddle.side {
+                Side::Left => {
+                    chosen.left = movement;
+                    chosen.serve_left = serve;
+                }
+                Side::Right => {
+                    chosen.right = movement;
+                    chosen.serve_right = serve;
                 }
             }
+        }
+
+        *tick_input = chosen;
+        if let Some(mut recorder) = recorder {
+            recorder.record(chosen);
+        }
+    }
+
+}
 
+// Devices lets the pong-core controllers read amethyst's input state
+pub struct Devices<'a> {
+    input: &'a InputHandler<StringBindings>,
+    // missing in headless runs, where there is no window to point at
+    screen: Option<&'a ScreenDimensions>,
+    arena_height: f32,
+    // set when playing in a terminal, which has no window to take keys from
+    terminal_keys: Option<&'a TerminalKeys>,
+}
+
+impl<'a> Devices<'a> {
+    pub fn new(
+        input: &'a InputHandler<StringBindings>,
+        screen: Option<&'a ScreenDimensions>,
+        arena_height: f32,
+    ) -> Devices<'a> {
+        Devices {
+            input,
+            screen,
+            arena_height,
+            terminal_keys: None,
         }
     }
 
+    pub fn with_terminal_keys(mut self, terminal_keys: Option<&'a TerminalKeys>) -> Devices<'a> {
+        self.terminal_keys = terminal_keys;
+        self
+    }
+}
+
+impl<'a> InputSource for Devices<'a> {
+    fn axis(&self, name: &str) -> Option<f32> {
+        match self.terminal_keys {
+            Some(keys) => keys.axis_value(&self.input.bindings, name),
+            None => self.input.axis_value(name),
+        }
+    }
+
+    fn gamepad_axis(&self, pad: u32) -> Option<f32> {
+        // gamepad sticks report up as negative
+        self.input
+            .controller_axis_value(pad, ControllerAxis::LeftY)
+            .map(|value| -value)
+    }
+
+    fn pointer_y(&self) -> Option<f32> {
+        // window coordinates start at the top left, the arena's at the bottom left
+        let (_, y) = self.input.mouse_position()?;
+        let screen = self.screen?;
+        Some((1.0 - y / screen.height()) * self.arena_height)
+    }
+
+    fn action(&self, name: &str) -> Option<bool> {
+        match self.terminal_keys {
+            Some(keys) => keys.action_is_down(&self.input.bindings, name),
+            None => self.input.action_is_down(name),
+        }
+    }
 }
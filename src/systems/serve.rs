@@ -0,0 +1,50 @@
+use amethyst::{
+    core::SystemDesc,
+    derive::SystemDesc,
+    ecs::prelude::{Join, Read, ReadExpect, System, SystemData, World, WriteExpect, WriteStorage},
+    ui::UiText,
+};
+use pong_core::{FixedTimestep, Input, MatchRules, PhysicsConfig, Serve, Side};
+
+use crate::pong::{Ball, ServeText};
+
+// ServeSystem holds the ball between points and launches it when the countdown ends or the
+// serving side's controller asks to serve.
+#[derive(SystemDesc)]
+pub struct ServeSystem;
+
+impl<'s> System<'s> for ServeSystem {
+    type SystemData = (
+        WriteStorage<'s, Ball>,
+        WriteExpect<'s, Serve>,
+        Read<'s, Input>,
+        ReadExpect<'s, MatchRules>,
+        ReadExpect<'s, PhysicsConfig>,
+        ReadExpect<'s, FixedTimestep>,
+        WriteStorage<'s, UiText>,
+        Option<ReadExpect<'s, ServeText>>,
+    );
+
+    fn run(&mut self, (mut balls, mut serve, input, rules, physics, clock, mut ui_text, serve_text): Self::SystemData) {
+        let pressed = input.serve(serve.server);
+
+        for ball in (&mut balls).join() {
+            serve.update(ball, pressed, &rules, &physics, clock.tick_seconds());
+        }
+
+        if let Some(ref serve_text) = serve_text {
+            if let Some(text) = ui_text.get_mut(serve_text.text) {
+                text.text = serve_label(&serve);
+            }
+        }
+    }
+}
+
+// the countdown shown while the ball waits to be served, pointing at the server
+pub fn serve_label(serve: &Serve) -> String {
+    match (serve.countdown, serve.server) {
+        (Some(countdown), Side::Left) => format!("< serve {}", countdown.ceil()),
+        (Some(countdown), Side::Right) => format!("serve {} >", countdown.ceil()),
+        (None, _) => String::new(),
+    }
+}
@@ -1,17 +1,20 @@
 use amethyst::{
     assets::AssetStorage,
     audio::{output::Output, Source},
-    core::transform::Transform,
     core::SystemDesc,
     derive::SystemDesc,
     ecs::{Read, ReadExpect},
-    ecs::prelude::{Join, System, SystemData, World, Write, WriteStorage},
+    ecs::prelude::{Join, System, SystemData, World, Write, WriteExpect, WriteStorage},
     ui::UiText,
 };
 
 use std::ops::Deref;
+use pong_core::score::check_point;
+use pong_core::{AudioConfig, FixedTimestep, GameConfig, MatchRules, MatchState, Serve};
+
 use crate::audio::{play_score_sound, Sounds};
-use crate::pong::{Ball, ScoreBoard, ScoreText, ARENA_WIDTH};
+use crate::instant_replay::History;
+use crate::pong::{Ball, ScoreBoard, ScoreText, Side};
 
 #[derive(SystemDesc)]
 pub struct WinnerSystem;
@@ -27,55 +30,72 @@ impl<'s> System<'s> for WinnerSystem {
      * asserting that ScoreText must already exist and will panic if it does not. We do this instead
      * of just using Read because we are manually adding the ScoreText resource to the game in
      * pong.rs > initialise_scoreboard instead of having the system create this resource for us
-     * automatically.
+     * automatically. Headless runs have neither the score text nor the sounds, so both are
+     * wrapped in an Option.
      */
 
     type SystemData = (
         WriteStorage<'s, Ball>,
-        WriteStorage<'s, Transform>,
         WriteStorage<'s, UiText>,
         Write<'s, ScoreBoard>,
-        ReadExpect<'s, ScoreText>,
+        Write<'s, MatchState>,
+        WriteExpect<'s, Serve>,
+        ReadExpect<'s, MatchRules>,
+        ReadExpect<'s, GameConfig>,
+        ReadExpect<'s, FixedTimestep>,
+        Option<ReadExpect<'s, ScoreText>>,
         Read<'s, AssetStorage<Source>>,
-        ReadExpect<'s, Sounds>,
+        Option<ReadExpect<'s, Sounds>>,
         Option<Read<'s, Output>>,
+        Read<'s, AudioConfig>,
+        Write<'s, History>,
     );
 
     fn run(&mut self, (
             mut balls, 
-            mut locals,
             mut ui_text,
             mut scores,
+            mut match_state,
+            mut serve,
+            rules,
+            config,
+            clock,
             score_text,
             storage,
             sounds,
             audio_output,
+            audio_config,
+            mut history,
         ): Self::SystemData) {
-        for (ball, transform) in (&mut balls, &mut locals).join() {
-            let ball_x = transform.translation().x;
-
-            let did_hit = if ball_x <= ball.radius {
-                scores.score_right = (scores.score_right + 1).min(999); // educational max - not useful
-                if let Some(text) = ui_text.get_mut(score_text.p2_score) {
-                    text.text = scores.score_right.to_string();
-                }
-                true
-            } else if ball_x >= ARENA_WIDTH - ball.radius {
-                scores.score_left = (scores.score_left + 1);
-                if let Some(text) = ui_text.get_mut(score_text.p1_score) {
-                    text.text = scores.score_left.to_string();
-                }
-                true
-            } else {
-                false
+        for ball in (&mut balls).join() {
+            let side = match check_point(ball, config.arena_width, config.arena_height, &mut scores) {
+                Some(side) => side,
+                None => continue,
             };
+            serve.after_point(side, &rules);
+            history.mark_point();
+            if let Some(ref score_text) = score_text {
+                let entity = match side {
+                    Side::Left => score_text.p1_score,
+                    Side::Right => score_text.p2_score,
+                };
+                if let Some(text) = ui_text.get_mut(entity) {
+                    text.text = scores.score(side).to_string();
+                }
+            }
 
-            if did_hit {
-                ball.velocity[0] = -ball.velocity[0];
-                transform.set_translation_x(ARENA_WIDTH / 2.0); // reset position
-                play_score_sound(&*sounds, &storage, audio_output.as_ref().map(|o| o.deref()));
+            if let Some(ref sounds) = sounds {
+                play_score_sound(
+                    sounds,
+                    &storage,
+                    audio_output.as_ref().map(|o| o.deref()),
+                    audio_config.effects_volume,
+                );
             }
         }
+
+        // the playing state moves on to the results once a winner is set
+        match_state.update(&rules, &scores, clock.tick_seconds());
     }
 }
 
@@ -0,0 +1,322 @@
+use std::io;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use amethyst::log::info;
+use pong_core::bot::{BotController, BotSettings};
+use pong_core::tournament::{next_round, play, record, series, series_winner, standings, Game, Outcome, Record};
+use pong_core::{AiController, Arena, Difficulty, PaddleController, Side};
+
+use crate::options::MatchSeed;
+
+// ticks after which a game goes to whoever leads (ten minutes at 120 Hz), so two players that
+// never miss can't hold up the tournament
+const DEFAULT_MAX_TICKS: u32 = 72_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    // everyone plays everyone
+    RoundRobin,
+    // knockout rounds until one entrant is left
+    Bracket,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "round-robin" => Ok(Format::RoundRobin),
+            "bracket" => Ok(Format::Bracket),
+            other => Err(format!("unknown format `{}`, expected round-robin or bracket", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Player {
+    Cpu(Difficulty),
+    // a bot program and its arguments
+    Exec(Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Entrant {
+    // as given on the command line
+    name: String,
+    player: Player,
+}
+
+impl FromStr for Entrant {
+    type Err = String;
+
+    // "cpu[:<difficulty>]" or "exec:<command>"
+    fn from_str(s: &str) -> Result<Entrant, String> {
+        let player = if s == "cpu" {
+            Player::Cpu(Difficulty::Medium)
+        } else if let Some(difficulty) = s.strip_prefix("cpu:") {
+            Player::Cpu(difficulty.parse()?)
+        } else if let Some(command) = s.strip_prefix("exec:") {
+            let command: Vec<String> = command.split_whitespace().map(String::from).collect();
+            if command.is_empty() {
+                return Err(format!("`{}` names no program", s));
+            }
+            Player::Exec(command)
+        } else {
+            return Err(format!("unknown entrant `{}`, expected cpu[:difficulty] or exec:<command>", s));
+        };
+        Ok(Entrant {
+            name: s.to_string(),
+            player,
+        })
+    }
+}
+
+// TournamentConfig holds what `pong tournament` was given on the command line
+pub struct TournamentConfig {
+    // in seed order
+    pub entrants: Vec<Entrant>,
+    pub format: Format,
+    // games per pairing, with the entrants swapping sides after each
+    pub games: u32,
+    pub threads: usize,
+    pub seed: MatchSeed,
+    pub max_ticks: u32,
+    pub bot: BotSettings,
+}
+
+impl Default for TournamentConfig {
+    fn default() -> TournamentConfig {
+        TournamentConfig {
+            entrants: Vec::new(),
+            format: Format::RoundRobin,
+            games: 2,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            seed: MatchSeed::from_clock(),
+            max_ticks: DEFAULT_MAX_TICKS,
+            bot: BotSettings::default(),
+        }
+    }
+}
+
+// a bot program started for one game, stopped when the game is over
+struct BotProcess(Child);
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// who plays a paddle for one game
+enum Seat {
+    Cpu(AiController),
+    Bot {
+        controller: BotController,
+        // only held, so the program is stopped along with the seat
+        _process: BotProcess,
+    },
+}
+
+impl Seat {
+    // a bot's program is started with the address to connect to as its last argument
+    fn take(entrant: &Entrant, side: Side, seed: u64, settings: &BotSettings) -> io::Result<Seat> {
+        match entrant.player {
+            Player::Cpu(difficulty) => Ok(Seat::Cpu(AiController::new(difficulty, seed.wrapping_add(side as u64)))),
+            Player::Exec(ref command) => {
+                // port 0 lets every game in parallel listen on a port of its own
+                let bot = BotController::listen("127.0.0.1:0", side, settings.clone())?;
+                // stdout is where the standings go
+                let child = Command::new(&command[0])
+                    .args(&command[1..])
+                    .arg(bot.local_addr()?.to_string())
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .map_err(|e| io::Error::new(e.kind(), format!("failed to start `{}`: {}", command.join(" "), e)))?;
+                Ok(Seat::Bot {
+                    controller: bot,
+                    _process: BotProcess(child),
+                })
+            }
+        }
+    }
+
+    fn controller(&mut self) -> &mut dyn PaddleController {
+        match self {
+            Seat::Cpu(controller) => controller,
+            Seat::Bot { controller, .. } => controller,
+        }
+    }
+
+    fn late(&self) -> u64 {
+        match self {
+            Seat::Cpu(_) => 0,
+            Seat::Bot { controller, .. } => controller.missed(),
+        }
+    }
+}
+
+fn play_game(arena: &Arena, game: Game, config: &TournamentConfig) -> io::Result<Outcome> {
+    let entrants = &config.entrants;
+    let [mut left, mut right] = [
+        Seat::take(&entrants[game.left], Side::Left, game.seed, &config.bot)?,
+        Seat::take(&entrants[game.right], Side::Right, game.seed, &config.bot)?,
+    ];
+    let outcome = play(arena, game, [left.controller(), right.controller()], config.max_ticks);
+    Ok(Outcome {
+        late: [left.late(), right.late()],
+        ..outcome
+    })
+}
+
+// plays `games` on `config.threads` threads, returning the outcomes in the order of `games`
+fn play_all(arena: &Arena, games: &[Game], config: &TournamentConfig) -> amethyst::Result<Vec<Outcome>> {
+    let entrants = &config.entrants;
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..config.threads.min(games.len()) {
+            let (sender, next, failed) = (sender.clone(), &next, &failed);
+            scope.spawn(move || {
+                while !failed.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let game = match games.get(index) {
+                        Some(&game) => game,
+                        None => break,
+                    };
+                    let outcome = play_game(arena, game, config);
+                    match outcome {
+                        Ok(ref outcome) => info!(
+                            "{} {} - {} {} after {} ticks",
+                            entrants[game.left].name,
+                            outcome.score_left,
+                            outcome.score_right,
+                            entrants[game.right].name,
+                            outcome.ticks
+                        ),
+                        // the games already started finish, but no new ones are started
+                        Err(_) => failed.store(true, Ordering::Relaxed),
+                    }
+                    let _ = sender.send((index, outcome));
+                }
+            });
+        }
+    });
+    drop(sender);
+
+    let mut outcomes = vec![None; games.len()];
+    for (index, outcome) in receiver {
+        outcomes[index] = Some(outcome.map_err(|e| amethyst::Error::from_string(e.to_string()))?);
+    }
+    Ok(outcomes.into_iter().flatten().collect())
+}
+
+// plays knockout rounds and returns the champion, printing each round as it ends
+fn bracket(arena: &Arena, config: &TournamentConfig, records: &mut [Record]) -> amethyst::Result<usize> {
+    let entrants = &config.entrants;
+    let seed = config.seed.0;
+    let mut alive: Vec<usize> = (0..entrants.len()).collect();
+    let mut round = 1;
+    while alive.len() > 1 {
+        let pairing = next_round(&alive);
+        let games: Vec<Game> = pairing
+            .pairs
+            .iter()
+            .flat_map(|&(a, b)| series(a, b, config.games, seed))
+            .collect();
+        let outcomes = play_all(arena, &games, config)?;
+        record(records, &games, &outcomes);
+
+        println!("round {}", round);
+        alive = pairing.byes.clone();
+        let per_pair = config.games as usize;
+        for (i, &(a, b)) in pairing.pairs.iter().enumerate() {
+            let range = i * per_pair..(i + 1) * per_pair;
+            let (winner, wins) = series_winner(a, b, &games[range.clone()], &outcomes[range]);
+            let (loser, score) = if winner == a { (b, wins) } else { (a, [wins[1], wins[0]]) };
+            println!("  {} beat {} {}-{}", entrants[winner].name, entrants[loser].name, score[0], score[1]);
+            alive.push(winner);
+        }
+        for bye in pairing.byes {
+            println!("  {} had a bye", entrants[bye].name);
+        }
+        // back in seed order for the next round
+        alive.sort_unstable();
+        round += 1;
+    }
+    Ok(alive[0])
+}
+
+fn print_standings(entrants: &[Entrant], records: &[Record]) {
+    let width = entrants.iter().map(|entrant| entrant.name.len()).max().unwrap_or(0).max("entrant".len());
+    println!(
+        "{:>4}  {:<width$}  {:>6}  {:>5}  {:>5}  {:>5}  {:>8}  {:>11}  {:>6}  {:>6}",
+        "rank",
+        "entrant",
+        "played",
+        "won",
+        "lost",
+        "drawn",
+        "win rate",
+        "points",
+        "diff",
+        "late",
+        width = width
+    );
+    for (rank, i) in standings(records).into_iter().enumerate() {
+        let record = &records[i];
+        let late = match entrants[i].player {
+            Player::Cpu(_) => "-".to_string(),
+            Player::Exec(_) => record.late.to_string(),
+        };
+        println!(
+            "{:>4}  {:<width$}  {:>6}  {:>5}  {:>5}  {:>5}  {:>7.1}%  {:>11}  {:>+6}  {:>6}",
+            rank + 1,
+            entrants[i].name,
+            record.played,
+            record.won,
+            record.lost,
+            record.drawn,
+            record.win_rate() * 100.0,
+            format!("{}:{}", record.points_for, record.points_against),
+            record.difference(),
+            late,
+            width = width
+        );
+    }
+}
+
+// Plays a tournament of headless games between computer players and bot programs on several
+// threads at once, then prints the standings, for tuning the computer's difficulties and
+// benchmarking bots against each other, e.g. overnight. No window is opened.
+pub fn run(config: &TournamentConfig, arena: &Arena) -> amethyst::Result<()> {
+    let entrants = &config.entrants;
+    let mut records = vec![Record::default(); entrants.len()];
+    match config.format {
+        Format::RoundRobin => {
+            let mut games = Vec::new();
+            for a in 0..entrants.len() {
+                for b in a + 1..entrants.len() {
+                    games.extend(series(a, b, config.games, config.seed.0));
+                }
+            }
+            let outcomes = play_all(arena, &games, config)?;
+            record(&mut records, &games, &outcomes);
+            print_standings(entrants, &records);
+        }
+        Format::Bracket => {
+            let champion = bracket(arena, config, &mut records)?;
+            println!();
+            print_standings(entrants, &records);
+            println!();
+            println!("champion: {}", entrants[champion].name);
+        }
+    }
+    Ok(())
+}
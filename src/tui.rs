@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use amethyst::{
+    core::timing::Time,
+    ecs::prelude::{Dispatcher, Join},
+    input::{Axis, Bindings, Button, StringBindings, VirtualKeyCode},
+    log::error,
+    prelude::*,
+};
+use crossterm::{
+    cursor,
+    event::{
+        self, Event as TerminalEvent, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::Print,
+    terminal,
+};
+use pong_core::{FixedTimestep, GameConfig, MatchState, Serve, Side};
+
+use crate::pong::{initialize_ball, initialize_paddles, reset_match, Ball, Paddle, ScoreBoard};
+use crate::replay::save_recording;
+use crate::systems::{physics_dispatcher, serve_label};
+
+// Most terminals only report key presses, repeated while a key is held, and never the release.
+// Without releases a key counts as held for this long after it was last reported, which covers
+// the usual delay before a held key starts repeating.
+const KEY_HOLD_SECONDS: f32 = 0.5;
+
+const LETTERS: [VirtualKeyCode; 26] = [
+    VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E,
+    VirtualKeyCode::F, VirtualKeyCode::G, VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J,
+    VirtualKeyCode::K, VirtualKeyCode::L, VirtualKeyCode::M, VirtualKeyCode::N, VirtualKeyCode::O,
+    VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R, VirtualKeyCode::S, VirtualKeyCode::T,
+    VirtualKeyCode::U, VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X, VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+];
+
+// TerminalKeys holds the keys pressed in the terminal. With it in the world the human
+// controllers read these instead of the window's keys, through the same bindings.
+#[derive(Default)]
+pub struct TerminalKeys {
+    // seconds each key stays held, or None if it is held until it is released
+    held: HashMap<VirtualKeyCode, Option<f32>>,
+}
+
+impl TerminalKeys {
+    fn press(&mut self, key: VirtualKeyCode, reports_releases: bool) {
+        let hold = if reports_releases { None } else { Some(KEY_HOLD_SECONDS) };
+        self.held.insert(key, hold);
+    }
+
+    fn release(&mut self, key: VirtualKeyCode) {
+        self.held.remove(&key);
+    }
+
+    // lets go of keys that haven't been reported for a while
+    fn update(&mut self, seconds: f32) {
+        self.held.retain(|_, hold| match hold {
+            Some(left) => {
+                *left -= seconds;
+                *left > 0.0
+            }
+            None => true,
+        });
+    }
+
+    fn is_down(&self, button: &Button) -> bool {
+        match button {
+            Button::Key(key) => self.held.contains_key(key),
+            _ => false,
+        }
+    }
+
+    pub fn axis_value(&self, bindings: &Bindings<StringBindings>, name: &str) -> Option<f32> {
+        bindings.axis(name).map(|axis| self.axis(axis))
+    }
+
+    pub fn action_is_down(&self, bindings: &Bindings<StringBindings>, name: &str) -> Option<bool> {
+        Some(
+            bindings
+                .action_bindings(name)
+                .any(|combination| combination.iter().all(|button| self.is_down(button))),
+        )
+    }
+
+    fn axis(&self, axis: &Axis) -> f32 {
+        match axis {
+            Axis::Emulated { pos, neg } => match (self.is_down(pos), self.is_down(neg)) {
+                (true, false) => 1.0,
+                (false, true) => -1.0,
+                _ => 0.0,
+            },
+            // the furthest deflection wins, as with amethyst's own input
+            Axis::Multiple(axes) => axes
+                .iter()
+                .map(|axis| self.axis(axis))
+                .fold(0.0, |furthest, value| if value.abs() > furthest.abs() { value } else { furthest }),
+            _ => 0.0,
+        }
+    }
+}
+
+// Terminal puts the terminal into raw mode on an alternate screen for as long as it lives
+struct Terminal {
+    out: Stdout,
+    reports_releases: bool,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        // terminals that understand it report key releases as well
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_releases {
+            execute!(out, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
+        Ok(Terminal { out, reports_releases })
+    }
+
+    // feeds the keys pressed since the last frame into `keys`; returns the keys that were
+    // pressed, for the state's own shortcuts
+    fn read_keys(&mut self, keys: &mut TerminalKeys) -> io::Result<Vec<KeyCode>> {
+        let mut pressed = Vec::new();
+        while event::poll(Duration::from_secs(0))? {
+            let key = match event::read()? {
+                TerminalEvent::Key(key) => key,
+                _ => continue,
+            };
+            // raw mode swallows ctrl-c, so it has to be handled here
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                pressed.push(KeyCode::Esc);
+                continue;
+            }
+            let virtual_key = virtual_key(key.code);
+            match key.kind {
+                KeyEventKind::Release => {
+                    if let Some(virtual_key) = virtual_key {
+                        keys.release(virtual_key);
+                    }
+                }
+                KeyEventKind::Press | KeyEventKind::Repeat => {
+                    if let Some(virtual_key) = virtual_key {
+                        keys.press(virtual_key, self.reports_releases);
+                    }
+                    pressed.push(key.code);
+                }
+            }
+        }
+        Ok(pressed)
+    }
+
+    fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        for (row, line) in lines.iter().enumerate() {
+            queue!(self.out, cursor::MoveTo(0, row as u16), Print(line))?;
+        }
+        self.out.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn virtual_key(code: KeyCode) -> Option<VirtualKeyCode> {
+    match code {
+        KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+            Some(LETTERS[(c.to_ascii_lowercase() as u8 - b'a') as usize])
+        }
+        KeyCode::Char(' ') => Some(VirtualKeyCode::Space),
+        KeyCode::Up => Some(VirtualKeyCode::Up),
+        KeyCode::Down => Some(VirtualKeyCode::Down),
+        KeyCode::Left => Some(VirtualKeyCode::Left),
+        KeyCode::Right => Some(VirtualKeyCode::Right),
+        KeyCode::Enter => Some(VirtualKeyCode::Return),
+        _ => None,
+    }
+}
+
+// TuiPong plays a match in the terminal. It runs the same gameplay systems as the headless
+// simulation, in real time, and draws the arena with text after every frame.
+#[derive(Default)]
+pub struct TuiPong {
+    terminal: Option<Terminal>,
+    physics: Option<Dispatcher<'static, 'static>>,
+}
+
+impl SimpleState for TuiPong {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        reset_match(world);
+        self.physics.replace(physics_dispatcher(world));
+
+        initialize_paddles(world, None);
+        initialize_ball(world, None);
+        world.insert(TerminalKeys::default());
+
+        match Terminal::open() {
+            Ok(terminal) => self.terminal = Some(terminal),
+            Err(e) => error!("failed to set up the terminal: {}", e),
+        }
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        // give the terminal back before printing anything
+        self.terminal = None;
+        save_recording(data.world);
+        let scores = data.world.read_resource::<ScoreBoard>();
+        println!("Final score: left {} - right {}", scores.score_left, scores.score_right);
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        let terminal = match self.terminal.as_mut() {
+            Some(terminal) => terminal,
+            None => return Trans::Quit,
+        };
+        let pressed = {
+            let mut keys = data.world.write_resource::<TerminalKeys>();
+            keys.update(data.world.fetch::<Time>().delta_seconds());
+            terminal.read_keys(&mut keys)
+        };
+        let pressed = match pressed {
+            Ok(pressed) => pressed,
+            Err(e) => {
+                error!("failed to read the terminal: {}", e);
+                return Trans::Quit;
+            }
+        };
+        if pressed.contains(&KeyCode::Esc) {
+            return Trans::Quit;
+        }
+        let over = data.world.read_resource::<MatchState>().is_over();
+        if over && pressed.iter().any(|&key| key == KeyCode::Char('r') || key == KeyCode::Char('R')) {
+            reset_match(data.world);
+        }
+
+        let ticks = {
+            let time = data.world.fetch::<Time>();
+            data.world.write_resource::<FixedTimestep>().advance(time.delta_seconds())
+        };
+        if let Some(physics) = self.physics.as_mut() {
+            for _ in 0..ticks {
+                physics.dispatch(data.world);
+            }
+        }
+
+        let size = terminal::size().unwrap_or((80, 24));
+        if let Err(e) = terminal.draw(&render(data.world, size)) {
+            error!("failed to draw to the terminal: {}", e);
+            return Trans::Quit;
+        }
+        Trans::None
+    }
+}
+
+// draws the world as lines of text filling a terminal of `columns` x `rows`: the score, the
+// arena in a frame and a status line
+fn render(world: &World, (columns, rows): (u16, u16)) -> Vec<String> {
+    let columns = (columns as usize).max(20);
+    let rows = (rows as usize).max(8);
+    let config = world.read_resource::<GameConfig>();
+    // inside the frame, between the score and status lines
+    let (width, height) = (columns - 2, rows - 4);
+
+    let column = |x: f32| ((x / config.arena_width * width as f32) as usize).min(width - 1);
+    let row = |y: f32| (((1.0 - y / config.arena_height) * height as f32) as usize).min(height - 1);
+
+    let mut grid = vec![vec![' '; width]; height];
+    for (y, line) in grid.iter_mut().enumerate() {
+        if y % 2 == 0 {
+            line[width / 2] = '┊';
+        }
+    }
+    for paddle in world.read_storage::<Paddle>().join() {
+        let [x, y] = paddle.position;
+        let (top, bottom) = (row(y + paddle.height * 0.5), row(y - paddle.height * 0.5));
+        for line in grid.iter_mut().take(bottom + 1).skip(top) {
+            line[column(x)] = '█';
+        }
+    }
+    for ball in world.read_storage::<Ball>().join() {
+        let [x, y] = ball.position;
+        grid[row(y)][column(x)] = '●';
+    }
+
+    let scores = world.read_resource::<ScoreBoard>();
+    let score = format!("{}  :  {}", scores.score_left, scores.score_right);
+    let status = match world.read_resource::<MatchState>().winner {
+        Some(Side::Left) => "Left player wins!   R: rematch   Esc: quit".to_string(),
+        Some(Side::Right) => "Right player wins!   R: rematch   Esc: quit".to_string(),
+        None => format!("{}   Esc: quit", serve_label(&world.read_resource::<Serve>())),
+    };
+
+    let mut lines = Vec::with_capacity(rows);
+    lines.push(centred(&score, columns));
+    lines.push(format!("┌{}┐", "─".repeat(width)));
+    for line in grid {
+        lines.push(format!("│{}│", line.into_iter().collect::<String>()));
+    }
+    lines.push(format!("└{}┘", "─".repeat(width)));
+    lines.push(centred(&status, columns));
+    lines
+}
+
+// pads `text` to `columns` characters with the text in the middle
+fn centred(text: &str, columns: usize) -> String {
+    let length = text.chars().count().min(columns);
+    let left = (columns - length) / 2;
+    let mut line = " ".repeat(left);
+    line.extend(text.chars().take(length));
+    line.push_str(&" ".repeat(columns - left - length));
+    line
+}
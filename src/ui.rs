@@ -0,0 +1,31 @@
+use amethyst::{
+    ecs::prelude::Entity,
+    prelude::*,
+    ui::{Anchor, FontHandle, UiText, UiTransform},
+};
+
+// A centred line of white text `y` pixels above the middle of the screen. Menu-style states
+// use these for their screens and delete them again in `on_stop`.
+pub fn create_text_line(world: &mut World, font: &FontHandle, text: &str, size: f32, y: f32) -> Entity {
+    let transform = UiTransform::new(
+        format!("text_{}", y), Anchor::Middle, Anchor::Middle,
+        0., y, 1., 500., size,
+    );
+    world
+        .create_entity()
+        .with(transform)
+        .with(UiText::new(font.clone(), text.to_string(), [1., 1., 1., 1.], size))
+        .build()
+}
+
+pub fn set_text(world: &mut World, entity: Entity, text: String) {
+    if let Some(ui_text) = world.write_storage::<UiText>().get_mut(entity) {
+        ui_text.text = text;
+    }
+}
+
+pub fn delete_entities(world: &mut World, entities: &mut Vec<Entity>) {
+    for entity in entities.drain(..) {
+        let _ = world.delete_entity(entity);
+    }
+}